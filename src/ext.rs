@@ -9,10 +9,13 @@
 //! Miscellaneous things used to integrate other code with Supercow, but which
 //! are not of interest to most client developers.
 
+use std::any::Any;
 use std::borrow::Borrow;
+use std::error::Error;
 use std::ffi::{CStr, OsStr};
+use std::fmt;
 use std::marker::PhantomData;
-use std::mem;
+use std::mem::{self, ManuallyDrop, MaybeUninit};
 use std::path::Path;
 use std::ptr;
 use std::rc::Rc;
@@ -82,29 +85,290 @@ pub trait TwoStepShared<OWNED, BORROWED : ?Sized> {
     unsafe fn deref_holder(&mut self) -> &mut Option<OWNED>;
 }
 
-macro_rules! twostepwrapper { ($outer:ident, $inner:ident) => {
-    /// Wrapper providing a `TwoStepShared` implementation.
-    pub struct $outer<T, B : ?Sized>($inner<Option<T>>, PhantomData<B>);
-    impl<T, B : ?Sized> Clone for $outer<T, B> {
+/// Declares a wrapper type providing a `TwoStepShared` implementation on top
+/// of a custom reference-counted type.
+///
+/// This is how `TwoStepRc` and `TwoStepArc` are defined. The wrapper can then
+/// be named in the `TwoStepShared(...)` clause of `supercow_features!`.
+///
+/// ## Syntax
+///
+/// ```
+/// #[macro_use] extern crate supercow;
+///
+/// use supercow::Supercow;
+///
+/// pub mod counted {
+///   /// Some custom reference-counted type.
+///   pub struct Counted<T>(::std::sync::Arc<T>);
+///   impl<T> Counted<T> {
+///     pub fn new(t: T) -> Self { Counted(::std::sync::Arc::new(t)) }
+///     pub fn get_mut(this: &mut Self) -> Option<&mut T> {
+///       ::std::sync::Arc::get_mut(&mut this.0)
+///     }
+///   }
+///   impl<T> Clone for Counted<T> {
+///     fn clone(&self) -> Self { Counted(self.0.clone()) }
+///   }
+///   impl<T> ::std::ops::Deref for Counted<T> {
+///     type Target = T;
+///     fn deref(&self) -> &T { &self.0 }
+///   }
+/// }
+///
+/// supercow_twostep!(
+///   /// Two-step wrapper around `Counted`.
+///   pub unsafe struct TwoStepCounted(counted::Counted));
+///
+/// supercow_features!(
+///   pub trait CountedFeatures: Clone, TwoStepShared(TwoStepCounted), Send,
+///                              Sync;
+///   pub type CountedSupercow, InlineCountedSupercow,
+///            CountedPhantomcow, InlineCountedPhantomcow);
+///
+/// # fn main() {
+/// let mut a: CountedSupercow<String, str> = Supercow::owned("foo".to_owned());
+/// let b = Supercow::share(&mut a);
+/// assert_eq!("foo", &*b);
+/// assert_eq!(a.as_ptr(), b.as_ptr());
+/// # }
+/// ```
+///
+/// ## Semantics
+///
+/// Defines a tuple struct `Name<T, B: ?Sized>` wrapping `Inner<Option<T>>`,
+/// which is `Clone` and `fmt::Debug`, and implements `ConstDeref` (with
+/// `Target = B`) and `TwoStepShared<T, B>` whenever `T: SafeBorrow<B>`.
+///
+/// `Inner` may be any path to a generic type with an associated function
+/// `new(value)`, an associated function `get_mut(&mut this) -> Option<&mut
+/// _>` returning `Some` only while `this` is the only reference to the
+/// value, a `Clone` implementation, and a `Deref` implementation.
+///
+/// ## Unsafety
+///
+/// The generated `ConstDeref` implementation is only sound if `Inner`
+/// behaves like `Rc`: the value must live at a fixed address for as long as
+/// any clone is alive, every clone must dereference to that same address, and
+/// `get_mut` must return `None` whenever more than one clone exists.
+///
+/// Since the macro cannot check any of this, the invocation must spell out
+/// `unsafe struct` to assert that `Inner` meets these requirements. It is
+/// rejected otherwise:
+///
+/// ```compile_fail
+/// #[macro_use] extern crate supercow;
+///
+/// supercow_twostep!(pub struct TwoStepStdRc(::std::rc::Rc));
+/// # fn main() { }
+/// ```
+#[macro_export]
+macro_rules! supercow_twostep {
+    ($(#[$meta:meta])* $vis:vis unsafe struct $outer:ident($($inner:tt)+)
+     $(;)?) => {
+        $(#[$meta])*
+        $vis struct $outer<T, B : ?Sized>(
+            $($inner)+<Option<T>>, ::std::marker::PhantomData<B>);
+
+        impl<T, B : ?Sized> Clone for $outer<T, B> {
+            fn clone(&self) -> Self {
+                $outer(self.0.clone(), ::std::marker::PhantomData)
+            }
+        }
+
+        impl<T : ::std::fmt::Debug, B : ?Sized> ::std::fmt::Debug
+        for $outer<T, B> {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter)
+                   -> ::std::fmt::Result {
+                f.debug_tuple(stringify!($outer)).field(&*self.0).finish()
+            }
+        }
+
+        // The value is never moved out of or replaced in the shared
+        // allocation once it has been populated (`deref_holder()` is only
+        // usable while the wrapper is still unique), so the borrowed address
+        // stays constant.
+        unsafe impl<T, B : ?Sized> $crate::ext::ConstDeref for $outer<T, B>
+        where T : $crate::ext::SafeBorrow<B> {
+            type Target = B;
+            /// ## Panics
+            ///
+            /// Panics if the value has not yet been populated via
+            /// `deref_holder()`.
+            fn const_deref(&self) -> &B {
+                ::std::borrow::Borrow::borrow(
+                    (*self.0).as_ref()
+                        .expect("Two-step wrapper not yet populated"))
+            }
+        }
+
+        impl<T, B : ?Sized> $crate::ext::TwoStepShared<T, B> for $outer<T, B>
+        where T : $crate::ext::SafeBorrow<B> {
+            fn new_two_step() -> Self {
+                $outer($($inner)+::new(None), ::std::marker::PhantomData)
+            }
+            unsafe fn deref_holder(&mut self) -> &mut Option<T> {
+                // Safety: No operation here is actually unsafe.
+                $($inner)+::get_mut(&mut self.0)
+                    .expect("Two-step wrapper already cloned")
+            }
+        }
+    };
+}
+
+supercow_twostep!(
+    /// Wrapper providing a `TwoStepShared` implementation on top of `Rc`.
+    pub unsafe struct TwoStepRc(Rc));
+supercow_twostep!(
+    /// Wrapper providing a `TwoStepShared` implementation on top of `Arc`.
+    pub unsafe struct TwoStepArc(Arc));
+
+macro_rules! refcounts {
+    ($outer:ident, $inner:ident, $weak:ident, $($weak_inner:tt)+) => {
+    impl<T, B : ?Sized> $outer<T, B> {
+        /// Returns the number of strong references to the shared value,
+        /// including `this`.
+        pub fn strong_count(this: &Self) -> usize {
+            $inner::strong_count(&this.0)
+        }
+
+        /// Creates a weak reference to the shared value.
+        pub fn downgrade(this: &Self) -> $weak<T, B> {
+            $weak($inner::downgrade(&this.0), PhantomData)
+        }
+    }
+
+    #[doc = concat!("Weak reference to the value of a `", stringify!($outer),
+                    "`, as returned by `downgrade()`.")]
+    pub struct $weak<T, B : ?Sized>(
+        $($weak_inner)+<Option<T>>, PhantomData<B>);
+
+    impl<T, B : ?Sized> $weak<T, B> {
+        #[doc = concat!("Returns a new `", stringify!($outer),
+                        "` sharing the value, or `None` if it has already \
+                         been dropped.")]
+        pub fn upgrade(&self) -> Option<$outer<T, B>> {
+            self.0.upgrade().map(|r| $outer(r, PhantomData))
+        }
+    }
+
+    impl<T, B : ?Sized> Clone for $weak<T, B> {
         fn clone(&self) -> Self {
-            $outer(self.0.clone(), PhantomData)
+            $weak(self.0.clone(), PhantomData)
+        }
+    }
+
+    impl<T, B : ?Sized> fmt::Debug for $weak<T, B> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.debug_tuple(stringify!($weak)).finish_non_exhaustive()
+        }
+    }
+} }
+refcounts!(TwoStepRc, Rc, WeakTwoStepRc, ::std::rc::Weak);
+refcounts!(TwoStepArc, Arc, WeakTwoStepArc, ::std::sync::Weak);
+
+macro_rules! shared_enum { ($name:ident, $rc:ident, $twostep:ident) => {
+    /// Statically-dispatched shared reference type built on
+    #[doc = concat!("`", stringify!($rc), "`.")]
+    ///
+    /// Unlike the default boxed feature trait objects, this needs no extra
+    /// allocation of its own and no virtual calls. It holds either a plain
+    #[doc = concat!("`", stringify!($rc),
+                    "<BORROWED>`, as passed to `Supercow::shared()`, or a")]
+    #[doc = concat!("`", stringify!($twostep),
+                    "` as created by `Supercow::share()`.")]
+    pub enum $name<OWNED, BORROWED : ?Sized> {
+        /// A plain reference-counted `BORROWED`.
+        Plain($rc<BORROWED>),
+        /// A two-step wrapper produced by `TwoStepShared`.
+        TwoStep($twostep<OWNED, BORROWED>),
+    }
+
+    impl<OWNED, BORROWED : ?Sized> Clone for $name<OWNED, BORROWED> {
+        fn clone(&self) -> Self {
+            match *self {
+                $name::Plain(ref r) => $name::Plain(r.clone()),
+                $name::TwoStep(ref r) => $name::TwoStep(r.clone()),
+            }
+        }
+    }
+
+    impl<OWNED : fmt::Debug, BORROWED : ?Sized + fmt::Debug> fmt::Debug
+    for $name<OWNED, BORROWED> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match *self {
+                $name::Plain(ref r) =>
+                    f.debug_tuple("Plain").field(r).finish(),
+                $name::TwoStep(ref r) =>
+                    f.debug_tuple("TwoStep").field(r).finish(),
+            }
+        }
+    }
+
+    // Both variants are `ConstDeref`, and the variant never changes.
+    unsafe impl<OWNED, BORROWED : ?Sized> ConstDeref for $name<OWNED, BORROWED>
+    where OWNED : SafeBorrow<BORROWED> {
+        type Target = BORROWED;
+        fn const_deref(&self) -> &BORROWED {
+            match *self {
+                $name::Plain(ref r) => r.const_deref(),
+                $name::TwoStep(ref r) => r.const_deref(),
+            }
+        }
+    }
+
+    // Moving an `Rc`/`Arc` into the enum does not move the value it points to.
+    unsafe impl<OWNED, BORROWED : ?Sized> SharedFrom<$rc<BORROWED>>
+    for $name<OWNED, BORROWED> {
+        fn shared_from(t: $rc<BORROWED>) -> Self {
+            $name::Plain(t)
         }
     }
 
-    impl<T, B : ?Sized> TwoStepShared<T, B> for $outer<T, B>
-    where T : SafeBorrow<B> {
+    unsafe impl<OWNED, BORROWED : ?Sized> SharedFrom<$twostep<OWNED, BORROWED>>
+    for $name<OWNED, BORROWED> {
+        fn shared_from(t: $twostep<OWNED, BORROWED>) -> Self {
+            $name::TwoStep(t)
+        }
+    }
+
+    impl<OWNED, BORROWED : ?Sized> TwoStepShared<OWNED, BORROWED>
+    for $name<OWNED, BORROWED>
+    where OWNED : SafeBorrow<BORROWED> {
         fn new_two_step() -> Self {
-            $outer($inner::new(None), PhantomData)
+            $name::TwoStep($twostep::new_two_step())
         }
-        unsafe fn deref_holder(&mut self) -> &mut Option<T> {
-            // Safety: No operation here is actually unsafe.
-            $inner::get_mut(&mut self.0)
-                .expect("Two-step wrapper already cloned")
+
+        unsafe fn deref_holder(&mut self) -> &mut Option<OWNED> {
+            match *self {
+                $name::TwoStep(ref mut r) => r.deref_holder(),
+                // The contract of `deref_holder()` guarantees that `self` was
+                // produced by `new_two_step()`.
+                $name::Plain(_) => unreachable!(),
+            }
         }
     }
 } }
-twostepwrapper!(TwoStepRc, Rc);
-twostepwrapper!(TwoStepArc, Arc);
+shared_enum!(RcShared, Rc, TwoStepRc);
+shared_enum!(ArcShared, Arc, TwoStepArc);
+
+/// Uninhabited shared reference type which rules out shared mode entirely.
+///
+/// No `SharedFrom` or `TwoStepShared` implementations exist for this type, so
+/// `Supercow::shared()`, `Supercow::share()`, and the like cannot be called on
+/// a `Supercow` using it. In exchange, the shared-mode branches of `Clone` and
+/// `Drop` are statically unreachable, and `InlineStorage` needs no space for a
+/// shared reference.
+///
+/// See `LiteSupercow`.
+#[derive(Debug)]
+pub enum NoShared { }
+
+impl Clone for NoShared {
+    fn clone(&self) -> Self {
+        match *self { }
+    }
+}
 
 /// The maximum displacement (relative to the start of the object) that a
 /// reference pointing into `self` from an instance of `SafeBorrow` may have.
@@ -113,6 +377,15 @@ twostepwrapper!(TwoStepArc, Arc);
 /// factors.
 pub const MAX_INTERNAL_BORROW_DISPLACEMENT: usize = 2048;
 
+/// Alias for `&'a T` used by `safe_borrow!`.
+///
+/// Writing `&'a $borrowed` inside the macro would make a trait object's
+/// lifetime bound default to `'a`, whereas `SafeBorrow<dyn Trait>` means
+/// `dyn Trait + 'static`. Passing the type through an alias keeps the
+/// `'static` default.
+#[doc(hidden)]
+pub type StaticRef<'a, T> = &'a T;
+
 /// Extension of `Borrow` used to allow `Supercow::to_mut()` to work safely.
 ///
 /// ## Unsafety
@@ -164,6 +437,19 @@ where T : Borrow<Path> {
         Path::new("")
     }
 }
+unsafe impl<T> SafeBorrow<dyn Any> for T
+where T : Borrow<dyn Any> {
+    fn borrow_replacement(_: &dyn Any) -> &dyn Any { &() }
+}
+unsafe impl<T> SafeBorrow<dyn Any + Send> for T
+where T : Borrow<dyn Any + Send> {
+    fn borrow_replacement(_: &(dyn Any + Send)) -> &(dyn Any + Send) { &() }
+}
+unsafe impl<T> SafeBorrow<dyn Any + Send + Sync> for T
+where T : Borrow<dyn Any + Send + Sync> {
+    fn borrow_replacement(_: &(dyn Any + Send + Sync))
+                          -> &(dyn Any + Send + Sync) { &() }
+}
 
 /// Marker trait identifying a pointer type which begins with an absolute
 /// address and contains no other address-dependent information.
@@ -185,12 +471,70 @@ where T : Borrow<Path> {
 /// should a future Rust version make such things possible.
 pub unsafe trait PointerFirstRef : Copy { }
 
-unsafe impl<T : Sized> PointerFirstRef for *const T { }
-unsafe impl<T> PointerFirstRef for *const [T] { }
-unsafe impl PointerFirstRef for *const str { }
-unsafe impl PointerFirstRef for *const ::std::ffi::CStr { }
-unsafe impl PointerFirstRef for *const ::std::ffi::OsStr { }
-unsafe impl PointerFirstRef for *const ::std::path::Path { }
+unsafe impl<T : ?Sized + PointerFirst> PointerFirstRef for *const T { }
+
+/// Marker trait identifying a type whose const pointers are
+/// `PointerFirstRef`.
+///
+/// This exists so that `PointerFirstRef` can be extended to trait objects of
+/// and DSTs defined in other crates, which could not otherwise implement it
+/// on `*const T` due to coherence rules. The `supercow_dst!` macro is the
+/// usual way to implement it.
+///
+/// Pointers to sized types, slices, and trait objects all begin with the data
+/// pointer (followed by the length or vtable pointer, respectively) and so
+/// satisfy the requirements.
+///
+/// ## Unsafety
+///
+/// Behaviour is undefined if `*const Self` does not satisfy the requirements
+/// of `PointerFirstRef`.
+pub unsafe trait PointerFirst { }
+
+unsafe impl<T : Sized> PointerFirst for T { }
+unsafe impl<T> PointerFirst for [T] { }
+unsafe impl PointerFirst for str { }
+unsafe impl PointerFirst for CStr { }
+unsafe impl PointerFirst for OsStr { }
+unsafe impl PointerFirst for Path { }
+macro_rules! pointer_first_dyn { ($tr:path) => {
+    unsafe impl PointerFirst for dyn $tr { }
+    unsafe impl PointerFirst for dyn $tr + Send { }
+    unsafe impl PointerFirst for dyn $tr + Send + Sync { }
+} }
+pointer_first_dyn!(Any);
+pointer_first_dyn!(Error);
+pointer_first_dyn!(fmt::Debug);
+pointer_first_dyn!(fmt::Display);
+
+/// Trait objects of `Any` (optionally with `Send` and `Sync`), which allow
+/// `Supercow::downcast()` to be written once for all of them.
+pub trait AnyDowncast {
+    /// Returns whether the value is a `T`.
+    fn is<T : Any>(&self) -> bool;
+    /// Returns a reference to the value if it is a `T`.
+    fn downcast_ref<T : Any>(&self) -> Option<&T>;
+    /// Converts `b` into a `Box<T>` if the value is a `T`, or returns it
+    /// unchanged otherwise.
+    fn downcast_box<T : Any>(b: Box<Self>) -> Result<Box<T>, Box<Self>>;
+}
+
+macro_rules! any_downcast { ($any:ty) => {
+    impl AnyDowncast for $any {
+        fn is<T : Any>(&self) -> bool {
+            <$any>::is::<T>(self)
+        }
+        fn downcast_ref<T : Any>(&self) -> Option<&T> {
+            <$any>::downcast_ref::<T>(self)
+        }
+        fn downcast_box<T : Any>(b: Box<Self>) -> Result<Box<T>, Box<Self>> {
+            b.downcast::<T>()
+        }
+    }
+} }
+any_downcast!(dyn Any);
+any_downcast!(dyn Any + Send);
+any_downcast!(dyn Any + Send + Sync);
 
 /// Like `std::convert::From`, but without the blanket implementations that
 /// cause problems for `supercow_features!`.
@@ -203,10 +547,10 @@ pub unsafe trait SharedFrom<T> {
     /// Converts the given `T` to `Self`.
     fn shared_from(t: T) -> Self;
 }
-unsafe impl <T> SharedFrom<Rc<T>> for Rc<T> {
+unsafe impl <T : ?Sized> SharedFrom<Rc<T>> for Rc<T> {
     fn shared_from(t: Rc<T>) -> Rc<T> { t }
 }
-unsafe impl <T> SharedFrom<Arc<T>> for Arc<T> {
+unsafe impl <T : ?Sized> SharedFrom<Arc<T>> for Arc<T> {
     fn shared_from(t: Arc<T>) -> Arc<T> { t }
 }
 
@@ -262,8 +606,15 @@ pub unsafe trait OwnedStorage<A, B> : Default {
     unsafe fn get_mut_a<'a>(&'a mut self, ptr: *mut ()) -> &'a mut A;
     /// See `get_mut_a`.
     unsafe fn get_mut_b<'a>(&'a mut self, ptr: *mut ()) -> &'a mut B;
-    /// Releases any allocations that would not be released by `Stored`
-    /// being dropped.
+    /// Disposes of the value stored by `allocate_a`.
+    ///
+    /// Between them, this call and dropping `self` must drop the value exactly
+    /// once and release anything allocated for it. The storage types in this
+    /// crate do all of that here, so that dropping the storage itself never
+    /// touches the value; `InlineStorage`, for example, drops the value in
+    /// place. Code which drives an `OwnedStorage` directly should therefore
+    /// always call this (or `deallocate_into_a`) for a live value instead of
+    /// relying on `self` being dropped.
     ///
     /// ## Unsafety
     ///
@@ -277,7 +628,8 @@ pub unsafe trait OwnedStorage<A, B> : Default {
     unsafe fn deallocate_a(&mut self, ptr: *mut ());
     /// See `deallocate_b`.
     unsafe fn deallocate_b(&mut self, ptr: *mut ());
-    /// Like `deallocate_a()`, but also return the owned value.
+    /// Like `deallocate_a()`, but moves the value out and returns it instead
+    /// of dropping it.
     unsafe fn deallocate_into_a(&mut self, ptr: *mut ()) -> A;
     /// See `deallocate_into_a`.
     unsafe fn deallocate_into_b(&mut self, ptr: *mut ()) -> B;
@@ -297,87 +649,102 @@ pub unsafe trait OwnedStorage<A, B> : Default {
 /// This makes allocation of owned `Supercow`s much faster, at the expense of
 /// making the `Supercow` itself much bigger (since it now must contain the
 /// whole object).
-#[derive(Clone, Copy, Debug)]
-pub struct InlineStorage<A, B>(InlineStorageImpl<A, B>);
+///
+/// The storage does not know which of the two values (if either) it holds, so
+/// it is only `Clone` and `Copy` when both `A` and `B` are `Copy`.
+pub struct InlineStorage<A, B>(MaybeUninit<InlineStorageImpl<A, B>>);
 
-#[derive(Clone, Copy, Debug)]
-enum InlineStorageImpl<A, B> {
-    None,
-    A(A),
-    B(B),
+// Which field (if either) is live is tracked by the `Supercow::mode` of the
+// owning `Supercow`, so no discriminant is needed. The storage never drops a
+// field by itself; `Supercow` calls `deallocate_a()` or `deallocate_b()` for
+// that.
+union InlineStorageImpl<A, B> {
+    a: ManuallyDrop<A>,
+    b: ManuallyDrop<B>,
 }
 
+impl<A : Copy, B : Copy> Clone for InlineStorage<A, B> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<A : Copy, B : Copy> Copy for InlineStorage<A, B> { }
+
+impl<A : Copy, B : Copy> Clone for InlineStorageImpl<A, B> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<A : Copy, B : Copy> Copy for InlineStorageImpl<A, B> { }
+
 impl<A, B> Default for InlineStorage<A, B> {
     fn default() -> Self {
-        InlineStorage(InlineStorageImpl::None)
+        InlineStorage(MaybeUninit::uninit())
     }
 }
 
+impl<A, B> fmt::Debug for InlineStorage<A, B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // The contents cannot be inspected without knowing the mode.
+        f.debug_struct("InlineStorage").finish_non_exhaustive()
+    }
+}
+
+// In the accessors below, the contract of `OwnedStorage` guarantees that the
+// field being accessed is the live one.
 unsafe impl<A, B> OwnedStorage<A, B> for InlineStorage<A, B> {
     #[inline]
     fn allocate_a(&mut self, value: A) -> *mut () {
-        self.0 = InlineStorageImpl::A(value);
+        self.0 = MaybeUninit::new(
+            InlineStorageImpl { a: ManuallyDrop::new(value) });
         2usize as *mut ()
     }
 
     #[inline]
     fn allocate_b(&mut self, value: B) -> *mut () {
-        self.0 = InlineStorageImpl::B(value);
+        self.0 = MaybeUninit::new(
+            InlineStorageImpl { b: ManuallyDrop::new(value) });
         2usize as *mut ()
     }
 
     #[inline]
     unsafe fn get_ptr_a<'a>(&'a self, _: *mut ()) -> &'a A {
-        match self.0 {
-            InlineStorageImpl::A(ref r) => r,
-            _ => unreachable!(),
-        }
+        &(*self.0.as_ptr()).a
     }
 
     #[inline]
     unsafe fn get_ptr_b<'a>(&'a self, _: *mut ()) -> &'a B {
-        match self.0 {
-            InlineStorageImpl::B(ref r) => r,
-            _ => unreachable!(),
-        }
+        &(*self.0.as_ptr()).b
     }
 
     #[inline]
     unsafe fn get_mut_a<'a>(&'a mut self, _: *mut ()) -> &'a mut A {
-        match self.0 {
-            InlineStorageImpl::A(ref mut r) => r,
-            _ => unreachable!(),
-        }
+        &mut (*self.0.as_mut_ptr()).a
     }
 
     #[inline]
     unsafe fn get_mut_b<'a>(&'a mut self, _: *mut ()) -> &'a mut B {
-        match self.0 {
-            InlineStorageImpl::B(ref mut r) => r,
-            _ => unreachable!(),
-        }
+        &mut (*self.0.as_mut_ptr()).b
     }
 
     #[inline]
-    unsafe fn deallocate_a(&mut self, _: *mut ()) { }
+    unsafe fn deallocate_a(&mut self, _: *mut ()) {
+        ManuallyDrop::drop(&mut (*self.0.as_mut_ptr()).a)
+    }
 
     #[inline]
-    unsafe fn deallocate_b(&mut self, _: *mut ()) { }
+    unsafe fn deallocate_b(&mut self, _: *mut ()) {
+        ManuallyDrop::drop(&mut (*self.0.as_mut_ptr()).b)
+    }
 
     #[inline]
     unsafe fn deallocate_into_a(&mut self, _: *mut ()) -> A {
-        match mem::replace(&mut self.0, InlineStorageImpl::None) {
-            InlineStorageImpl::A(v) => v,
-            _ => unreachable!(),
-        }
+        ManuallyDrop::take(&mut (*self.0.as_mut_ptr()).a)
     }
 
     #[inline]
     unsafe fn deallocate_into_b(&mut self, _: *mut ()) -> B {
-        match mem::replace(&mut self.0, InlineStorageImpl::None) {
-            InlineStorageImpl::B(v) => v,
-            _ => unreachable!(),
-        }
+        ManuallyDrop::take(&mut (*self.0.as_mut_ptr()).b)
     }
 
     #[inline]
@@ -402,95 +769,270 @@ pub struct BoxedStorage;
 unsafe impl<A, B> OwnedStorage<A, B> for BoxedStorage {
     #[inline]
     fn allocate_a(&mut self, value: A) -> *mut () {
-        if mem::size_of::<A>() > 0 {
-            let boxed: Box<Aligned<A>> = Box::new(([], value));
-            let address = Box::into_raw(boxed);
-            unsafe { &mut (*address).1 as *mut A as *mut () }
-        } else {
-            // Handle ZSTs specially, since `Box` "allocates" them at address
-            // 1.
-            2 as *mut ()
-        }
+        box_allocate(value)
     }
 
     #[inline]
     fn allocate_b(&mut self, value: B) -> *mut () {
-        if mem::size_of::<B>() > 0 {
-            let boxed: Box<Aligned<B>> = Box::new(([], value));
-            let address = Box::into_raw(boxed);
-            unsafe { &mut (*address).1 as *mut B as *mut () }
-        } else {
-            // Handle ZSTs specially, since `Box` "allocates" them at address
-            // 1.
-            2 as *mut ()
-        }
+        box_allocate(value)
     }
 
     #[inline]
     unsafe fn get_ptr_a<'a>(&'a self, ptr: *mut ()) -> &'a A {
-        &(*(ptr as *const Aligned<A>)).1
+        box_get(ptr)
     }
 
     #[inline]
     unsafe fn get_ptr_b<'a>(&'a self, ptr: *mut ()) -> &'a B {
-        &(*(ptr as *const Aligned<B>)).1
+        box_get(ptr)
     }
 
     #[inline]
     unsafe fn get_mut_a<'a>(&'a mut self, ptr: *mut ()) -> &'a mut A {
-        &mut(*(ptr as *mut Aligned<A>)).1
+        box_get_mut(ptr)
     }
 
     #[inline]
     unsafe fn get_mut_b<'a>(&'a mut self, ptr: *mut ()) -> &'a mut B {
-        &mut(*(ptr as *mut Aligned<B>)).1
+        box_get_mut(ptr)
     }
 
     #[inline]
     unsafe fn deallocate_a(&mut self, ptr: *mut ()) {
-        if mem::size_of::<A>() > 0 {
-            drop(Box::from_raw(ptr as *mut Aligned<A>))
-        }
+        box_deallocate::<A>(ptr)
     }
 
     #[inline]
     unsafe fn deallocate_b(&mut self, ptr: *mut ()) {
-        if mem::size_of::<B>() > 0 {
-            drop(Box::from_raw(ptr as *mut B))
-        }
+        box_deallocate::<B>(ptr)
     }
 
     #[inline]
     unsafe fn deallocate_into_a(&mut self, ptr: *mut ()) -> A {
-        if mem::size_of::<A>() > 0 {
-            let t = *Box::from_raw(ptr as *mut Aligned<A>);
-            t.1
+        box_deallocate_into(ptr)
+    }
+
+    #[inline]
+    unsafe fn deallocate_into_b(&mut self, ptr: *mut ()) -> B {
+        box_deallocate_into(ptr)
+    }
+
+    #[inline]
+    fn is_internal_storage() -> bool { false }
+}
+
+// The boxing strategy shared by `BoxedStorage` and the out-of-line case of
+// `SmallStorage`. Pointers produced by `box_allocate::<T>` must only be passed
+// to the other `box_*` functions with the same `T`.
+
+#[inline]
+fn box_allocate<T>(value: T) -> *mut () {
+    if mem::size_of::<T>() > 0 {
+        let boxed: Box<Aligned<T>> = Box::new(([], value));
+        let address = Box::into_raw(boxed);
+        unsafe { &mut (*address).1 as *mut T as *mut () }
+    } else {
+        // Handle ZSTs specially, since `Box` "allocates" them at address
+        // 1.
+        2 as *mut ()
+    }
+}
+
+#[inline]
+unsafe fn box_get<'a, T>(ptr: *mut ()) -> &'a T {
+    &(*(ptr as *const Aligned<T>)).1
+}
+
+#[inline]
+unsafe fn box_get_mut<'a, T>(ptr: *mut ()) -> &'a mut T {
+    &mut(*(ptr as *mut Aligned<T>)).1
+}
+
+#[inline]
+unsafe fn box_deallocate<T>(ptr: *mut ()) {
+    if mem::size_of::<T>() > 0 {
+        drop(Box::from_raw(ptr as *mut Aligned<T>))
+    }
+}
+
+#[inline]
+unsafe fn box_deallocate_into<T>(ptr: *mut ()) -> T {
+    if mem::size_of::<T>() > 0 {
+        let t = *Box::from_raw(ptr as *mut Aligned<T>);
+        t.1
+    } else {
+        ptr::read(ptr as *mut T)
+    }
+}
+
+/// Causes the `OWNED` or `SHARED` value of a `Supercow` to be stored inline
+/// if it fits in `N` bytes, and in a `Box` otherwise.
+///
+/// A value fits if its size is at most `N` and its alignment is no greater
+/// than that of a pointer. The decision is made per type at compile time, so
+/// there is no runtime tag; a large `SHARED` does not force a small `OWNED`
+/// onto the heap, nor vice versa.
+///
+/// Unlike `InlineStorage`, the size of the `Supercow` is bounded by `N`
+/// (rounded up to pointer alignment) regardless of `OWNED` and `SHARED`, which
+/// makes this a reasonable choice for generic code that may nest `Supercow`s
+/// or instantiate them with arbitrarily large types. Values which are stored
+/// inline pay the same `Deref` cost as with `InlineStorage`.
+pub struct SmallStorage<A, B, const N: usize> {
+    buf: MaybeUninit<SmallBuffer<N>>,
+    _values: PhantomData<(A, B)>,
+}
+
+#[repr(C)]
+struct SmallBuffer<const N: usize> {
+    _align: [usize; 0],
+    _bytes: [u8; N],
+}
+
+impl<A, B, const N: usize> SmallStorage<A, B, N> {
+    #[inline]
+    fn is_inline<T>() -> bool {
+        mem::size_of::<T>() <= N &&
+            mem::align_of::<T>() <= mem::align_of::<usize>()
+    }
+
+    #[inline]
+    fn allocate<T>(&mut self, value: T) -> *mut () {
+        if Self::is_inline::<T>() {
+            unsafe { ptr::write(self.buf.as_mut_ptr() as *mut T, value); }
+            // Same marker as `InlineStorage`; the real address is only known
+            // once the `Supercow` stops moving.
+            2 as *mut ()
         } else {
-            ptr::read(ptr as *mut A)
+            box_allocate(value)
         }
     }
 
     #[inline]
-    unsafe fn deallocate_into_b(&mut self, ptr: *mut ()) -> B {
-        if mem::size_of::<B>() > 0 {
-            let t = *Box::from_raw(ptr as *mut Aligned<B>);
-            t.1
+    unsafe fn get<T>(&self, ptr: *mut ()) -> &T {
+        if Self::is_inline::<T>() {
+            &*(self.buf.as_ptr() as *const T)
         } else {
-            ptr::read(ptr as *mut B)
+            box_get(ptr)
         }
     }
 
     #[inline]
-    fn is_internal_storage() -> bool { false }
+    unsafe fn get_mut<T>(&mut self, ptr: *mut ()) -> &mut T {
+        if Self::is_inline::<T>() {
+            &mut *(self.buf.as_mut_ptr() as *mut T)
+        } else {
+            box_get_mut(ptr)
+        }
+    }
+
+    #[inline]
+    unsafe fn deallocate<T>(&mut self, ptr: *mut ()) {
+        if Self::is_inline::<T>() {
+            ptr::drop_in_place(self.buf.as_mut_ptr() as *mut T)
+        } else {
+            box_deallocate::<T>(ptr)
+        }
+    }
+
+    #[inline]
+    unsafe fn deallocate_into<T>(&mut self, ptr: *mut ()) -> T {
+        if Self::is_inline::<T>() {
+            ptr::read(self.buf.as_ptr() as *const T)
+        } else {
+            box_deallocate_into(ptr)
+        }
+    }
+}
+
+impl<A, B, const N: usize> Default for SmallStorage<A, B, N> {
+    fn default() -> Self {
+        SmallStorage {
+            buf: MaybeUninit::uninit(),
+            _values: PhantomData,
+        }
+    }
+}
+
+impl<A, B, const N: usize> fmt::Debug for SmallStorage<A, B, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SmallStorage").finish_non_exhaustive()
+    }
+}
+
+unsafe impl<A, B, const N: usize> OwnedStorage<A, B>
+for SmallStorage<A, B, N> {
+    #[inline]
+    fn allocate_a(&mut self, value: A) -> *mut () {
+        self.allocate(value)
+    }
+
+    #[inline]
+    fn allocate_b(&mut self, value: B) -> *mut () {
+        self.allocate(value)
+    }
+
+    #[inline]
+    unsafe fn get_ptr_a(&self, ptr: *mut ()) -> &A {
+        self.get(ptr)
+    }
+
+    #[inline]
+    unsafe fn get_ptr_b(&self, ptr: *mut ()) -> &B {
+        self.get(ptr)
+    }
+
+    #[inline]
+    unsafe fn get_mut_a(&mut self, ptr: *mut ()) -> &mut A {
+        self.get_mut(ptr)
+    }
+
+    #[inline]
+    unsafe fn get_mut_b(&mut self, ptr: *mut ()) -> &mut B {
+        self.get_mut(ptr)
+    }
+
+    #[inline]
+    unsafe fn deallocate_a(&mut self, ptr: *mut ()) {
+        self.deallocate::<A>(ptr)
+    }
+
+    #[inline]
+    unsafe fn deallocate_b(&mut self, ptr: *mut ()) {
+        self.deallocate::<B>(ptr)
+    }
+
+    #[inline]
+    unsafe fn deallocate_into_a(&mut self, ptr: *mut ()) -> A {
+        self.deallocate_into(ptr)
+    }
+
+    #[inline]
+    unsafe fn deallocate_into_b(&mut self, ptr: *mut ()) -> B {
+        self.deallocate_into(ptr)
+    }
+
+    #[inline]
+    fn is_internal_storage() -> bool {
+        Self::is_inline::<A>() || Self::is_inline::<B>()
+    }
 }
 
 /// Optionally stores a pointer to a value.
 ///
 /// It is doubtful that there are any types besides `()` and `*mut T` which
 /// could implement this usefully.
+///
+/// Note that `new()` takes the initial pointer as an argument. It used to take
+/// none and return an unspecified value, which cannot be produced soundly for
+/// a `*const T` when `T` is unsized, so implementations written against the
+/// old signature need to accept and store the pointer instead.
 pub unsafe trait PtrWrite<T : ?Sized> : Copy {
-    /// Returns an instance of `Self` with no particular value.
-    fn new() -> Self;
+    /// Returns an instance of `Self` holding the given pointer.
+    ///
+    /// The implementation must not inspect the given pointer, and must not
+    /// panic.
+    fn new(t: *const T) -> Self;
 
     /// Writes the given pointer into `self`.
     ///
@@ -503,7 +1045,7 @@ pub unsafe trait PtrWrite<T : ?Sized> : Copy {
 
 unsafe impl<T : ?Sized> PtrWrite<T> for () {
     #[inline(always)]
-    fn new() -> Self { () }
+    fn new(_: *const T) -> Self { () }
 
     #[inline(always)]
     fn store_ptr(&mut self, _: *const T) { }
@@ -511,9 +1053,7 @@ unsafe impl<T : ?Sized> PtrWrite<T> for () {
 
 unsafe impl<T : ?Sized> PtrWrite<T> for *const T {
     #[inline(always)]
-    fn new() -> Self {
-        unsafe { mem::uninitialized() }
-    }
+    fn new(t: *const T) -> Self { t }
 
     #[inline(always)]
     fn store_ptr(&mut self, t: *const T) {
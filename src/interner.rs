@@ -0,0 +1,259 @@
+// Copyright 2016 Jason Lingle
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Deduplication of values into shared `Supercow`s.
+//!
+//! An `Interner` hands out shared-mode `Supercow`s such that all equal values
+//! interned through it point to the same allocation.
+//!
+//! ```
+//! use supercow::interner::Interner;
+//!
+//! let mut interner: Interner<String, str> = Interner::new();
+//! let a = interner.intern("foo");
+//! let b = interner.intern_owned("foo".to_owned());
+//! assert_eq!(a.as_ptr(), b.as_ptr());
+//!
+//! drop(a);
+//! drop(b);
+//! // Nothing references "foo" any more, so it has already been freed; its
+//! // stale entry can now be evicted.
+//! assert_eq!(1, interner.sweep());
+//! assert!(interner.is_empty());
+//! ```
+
+use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+
+use super::{DefaultFeatures, Supercow};
+use super::ext::*;
+
+/// Deduplicates values, returning shared `Supercow`s which all point to a
+/// single allocation per distinct value.
+///
+/// The interner only holds weak references to the values it has seen, so a
+/// value is freed as soon as the last `Supercow` referencing it is dropped.
+/// The entry for such a value is evicted the next time a lookup of an equal
+/// value comes across it, or by `sweep()`.
+pub struct Interner<OWNED, BORROWED : ?Sized = OWNED> {
+    // Entries are bucketed by the hash of their value, since the value of a
+    // dead entry can no longer be hashed or compared.
+    entries: HashMap<u64, Vec<WeakTwoStepArc<OWNED, BORROWED>>>,
+    hasher: RandomState,
+    len: usize,
+}
+
+impl<OWNED, BORROWED : ?Sized> Default for Interner<OWNED, BORROWED>
+where OWNED : SafeBorrow<BORROWED>,
+      BORROWED : Hash + Eq {
+    fn default() -> Self {
+        Interner {
+            entries: HashMap::new(),
+            hasher: RandomState::new(),
+            len: 0,
+        }
+    }
+}
+
+impl<OWNED, BORROWED : ?Sized> Interner<OWNED, BORROWED>
+where OWNED : SafeBorrow<BORROWED>,
+      BORROWED : Hash + Eq + 'static,
+      *const BORROWED : PointerFirstRef,
+      Box<dyn DefaultFeatures<'static> + 'static> :
+          SharedFrom<TwoStepArc<OWNED, BORROWED>> {
+    /// Creates a new, empty `Interner`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns a shared `Supercow` equal to `value`.
+    ///
+    /// If no equal value is currently alive, `value` is cloned into a new
+    /// shared allocation.
+    pub fn intern(&mut self, value: &BORROWED)
+                  -> Supercow<'static, OWNED, BORROWED>
+    where BORROWED : ToOwned<Owned = OWNED> {
+        let hash = self.hasher.hash_one(value);
+        if let Some(shared) = self.find(hash, value) {
+            return Supercow::shared(shared);
+        }
+
+        self.insert(hash, value.to_owned())
+    }
+
+    /// Returns a shared `Supercow` equal to `value`.
+    ///
+    /// If an equal value is currently alive, `value` is dropped and the
+    /// existing allocation is shared instead.
+    pub fn intern_owned(&mut self, value: OWNED)
+                        -> Supercow<'static, OWNED, BORROWED> {
+        let hash = self.hasher.hash_one(value.borrow());
+        if let Some(shared) = self.find(hash, value.borrow()) {
+            return Supercow::shared(shared);
+        }
+
+        self.insert(hash, value)
+    }
+
+    /// Looks for a live value equal to `value` in the bucket for `hash`,
+    /// evicting any dead entries found along the way.
+    fn find(&mut self, hash: u64, value: &BORROWED)
+            -> Option<TwoStepArc<OWNED, BORROWED>> {
+        let mut found = None;
+        let emptied = {
+            let bucket = self.entries.get_mut(&hash)?;
+            let before = bucket.len();
+            bucket.retain(|weak| match weak.upgrade() {
+                Some(strong) => {
+                    if found.is_none() && strong.const_deref() == value {
+                        found = Some(strong);
+                    }
+                    true
+                },
+                None => false,
+            });
+            self.len -= before - bucket.len();
+            bucket.is_empty()
+        };
+
+        if emptied {
+            self.entries.remove(&hash);
+        }
+        found
+    }
+
+    fn insert(&mut self, hash: u64, value: OWNED)
+              -> Supercow<'static, OWNED, BORROWED> {
+        let mut shared = TwoStepArc::new_two_step();
+        // Safety: `shared` was just produced by `new_two_step()` and has not
+        // been cloned.
+        *unsafe { shared.deref_holder() } = Some(value);
+        self.entries.entry(hash).or_default()
+            .push(TwoStepArc::downgrade(&shared));
+        self.len += 1;
+        Supercow::shared(shared)
+    }
+
+    /// Evicts the entries of every value which has been freed because nothing
+    /// references it any more.
+    ///
+    /// Returns the number of entries evicted.
+    pub fn sweep(&mut self) -> usize {
+        let before = self.len;
+        let mut len = 0;
+        self.entries.retain(|_, bucket| {
+            bucket.retain(|weak| weak.upgrade().is_some());
+            len += bucket.len();
+            !bucket.is_empty()
+        });
+        self.len = len;
+        before - len
+    }
+
+    /// Returns the number of entries currently held.
+    ///
+    /// This includes entries for freed values which have not been evicted
+    /// yet.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether this interner holds no entries.
+    pub fn is_empty(&self) -> bool {
+        0 == self.len
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn equal_values_share_allocation() {
+        let mut interner: Interner<String, str> = Interner::new();
+        let a = interner.intern("hello");
+        let b = interner.intern("hello");
+        let c = interner.intern_owned("hello".to_owned());
+        let d = interner.intern("world");
+
+        assert_eq!("hello", &*a);
+        assert_eq!("world", &*d);
+        assert_eq!(a.as_ptr(), b.as_ptr());
+        assert_eq!(a.as_ptr(), c.as_ptr());
+        assert!(a.as_ptr() != d.as_ptr());
+        assert_eq!(2, interner.len());
+    }
+
+    #[test]
+    fn interned_values_are_shared() {
+        let mut interner: Interner<u32> = Interner::new();
+        let a = interner.intern(&42);
+        assert_eq!(None, Supercow::extract_ref(&a));
+        assert!(Supercow::clone_non_owned(&a).is_some());
+    }
+
+    #[test]
+    fn sweep_evicts_unused() {
+        let mut interner: Interner<String, str> = Interner::new();
+        let a = interner.intern("hello");
+        let b = interner.intern("world");
+        let a2 = a.clone();
+
+        assert_eq!(0, interner.sweep());
+        drop(b);
+        assert_eq!(1, interner.sweep());
+        assert_eq!(1, interner.len());
+        drop(a);
+        assert_eq!(0, interner.sweep());
+        drop(a2);
+        assert_eq!(1, interner.sweep());
+        assert!(interner.is_empty());
+
+        // Values can be interned again after being evicted.
+        assert_eq!("hello", &*interner.intern("hello"));
+    }
+
+    #[test]
+    fn values_freed_without_sweep() {
+        use std::borrow::Borrow;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+        #[derive(PartialEq, Eq, Hash)]
+        struct Tracked(String);
+        impl Drop for Tracked {
+            fn drop(&mut self) {
+                DROPS.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+        impl Borrow<str> for Tracked {
+            fn borrow(&self) -> &str { &self.0 }
+        }
+
+        let mut interner: Interner<Tracked, str> = Interner::new();
+        let a = interner.intern_owned(Tracked("hello".to_owned()));
+        let b = interner.intern_owned(Tracked("hello".to_owned()));
+        assert_eq!(a.as_ptr(), b.as_ptr());
+        // The duplicate passed to the second call is dropped immediately.
+        assert_eq!(1, DROPS.load(Ordering::SeqCst));
+
+        drop(a);
+        drop(b);
+        assert_eq!(2, DROPS.load(Ordering::SeqCst));
+        assert_eq!(1, interner.len());
+
+        // Looking up an equal value evicts the dead entry it replaces.
+        let c = interner.intern_owned(Tracked("hello".to_owned()));
+        assert_eq!(1, interner.len());
+        assert_eq!(0, interner.sweep());
+        drop(c);
+        assert_eq!(3, DROPS.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,449 @@
+// Copyright 2016 Jason Lingle
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Iterators over the contents of `Supercow`s which preserve the ownership
+//! mode of the container in the items they yield.
+//!
+//! A `Supercow<Vec<T>, [T]>` can be iterated by value to produce a
+//! `Supercow<T>` for each element:
+//!
+//! - If the container is owned, the elements are moved out into owned
+//!   `Supercow`s.
+//!
+//! - If the container is borrowed, each item borrows the corresponding
+//!   element with the same lifetime.
+//!
+//! - If the container is shared, each item holds a clone of the shared
+//!   reference and points at the corresponding element within it.
+//!
+//! The item type keeps the `SHARED` and `STORAGE` types of the container, so
+//! this is only available for `STORAGE` types which can store both `Vec<T>`
+//! and `T`, such as `BoxedStorage`.
+//!
+//! ```
+//! use supercow::Supercow;
+//!
+//! let v: Supercow<Vec<String>, [String]> =
+//!   Supercow::owned(vec!["a".to_owned(), "b".to_owned()]);
+//! let items: Vec<Supercow<String>> = v.into_iter().collect();
+//! assert_eq!("a", &*items[0]);
+//! assert_eq!("b", &*items[1]);
+//! ```
+//!
+//! `Supercow<String, str>` (or any other `Supercow` borrowing `str`) can
+//! similarly be split into lines with `Supercow::lines()`, where each line
+//! shares the storage of the original string as with `Supercow::slice()`.
+//! `Supercow::into_chars()` iterates its `char`s while keeping hold of the
+//! `Supercow`, so that no copy of the string is needed.
+
+use std::marker::PhantomData;
+use std::slice;
+use std::vec;
+
+use super::Supercow;
+use super::SupercowMode::*;
+use super::ext::*;
+
+/// By-value iterator over a `Supercow<Vec<T>, [T]>`.
+///
+/// See the module documentation for details.
+pub struct IntoIter<'a, T : 'a, SHARED, STORAGE> {
+    inner: IntoIterImpl<'a, T, SHARED>,
+    _storage: PhantomData<STORAGE>,
+}
+
+enum IntoIterImpl<'a, T : 'a, SHARED> {
+    Owned(vec::IntoIter<T>),
+    Borrowed(slice::Iter<'a, T>),
+    // The base pointer is only valid as long as the `SHARED` value is alive;
+    // `start` and `end` are element indices of the yet-unvisited range.
+    Shared(SHARED, *const T, usize, usize),
+}
+
+// The raw pointer in `IntoIterImpl::Shared` suppresses the automatic impls.
+// The bounds here are those of the items being yielded.
+unsafe impl<'a, T : 'a, SHARED, STORAGE> Send
+for IntoIter<'a, T, SHARED, STORAGE>
+where T : Send + Sync, SHARED : Send { }
+unsafe impl<'a, T : 'a, SHARED, STORAGE> Sync
+for IntoIter<'a, T, SHARED, STORAGE>
+where T : Sync, SHARED : Sync { }
+
+impl<'a, T : 'a, SHARED, STORAGE> IntoIterator
+for Supercow<'a, Vec<T>, [T], SHARED, STORAGE>
+where SHARED : Clone,
+      STORAGE : OwnedStorage<Vec<T>, SHARED> + OwnedStorage<T, SHARED> {
+    type Item = Supercow<'a, T, T, SHARED, STORAGE>;
+    type IntoIter = IntoIter<'a, T, SHARED, STORAGE>;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        let inner = match self.mode() {
+            Owned(ptr) => {
+                let v = unsafe {
+                    // Safety: `mode` indicates storage a is allocated.
+                    <STORAGE as OwnedStorage<Vec<T>, SHARED>>::
+                    deallocate_into_a(&mut self.storage, ptr)
+                };
+                // Storage a is now released, so `self` must not try to drop
+                // it again.
+                self.mode = super::BORROWED_MODE;
+                IntoIterImpl::Owned(v.into_iter())
+            },
+
+            // unwrap() won't panic since we know we're in borrowed mode.
+            Borrowed => IntoIterImpl::Borrowed(
+                Supercow::extract_ref(&self).unwrap().iter()),
+
+            Shared(s) => {
+                let shared = unsafe {
+                    // Safety: `mode` indicates storage b is allocated.
+                    <STORAGE as OwnedStorage<Vec<T>, SHARED>>::
+                    get_ptr_b(&self.storage, s)
+                }.clone();
+                // The slice lives wherever `shared` keeps it, so the pointer
+                // remains valid after `self` is dropped.
+                IntoIterImpl::Shared(shared, self.as_ptr(), 0, self.len())
+            },
+        };
+
+        IntoIter { inner, _storage: PhantomData }
+    }
+}
+
+impl<'a, T : 'a, SHARED, STORAGE> Iterator
+for IntoIter<'a, T, SHARED, STORAGE>
+where SHARED : Clone,
+      STORAGE : OwnedStorage<T, SHARED> {
+    type Item = Supercow<'a, T, T, SHARED, STORAGE>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.inner {
+            IntoIterImpl::Owned(ref mut it) => it.next().map(Supercow::owned),
+            IntoIterImpl::Borrowed(ref mut it) =>
+                it.next().map(Supercow::borrowed),
+            IntoIterImpl::Shared(ref shared, base, ref mut start, end) => {
+                if *start == end {
+                    return None;
+                }
+
+                // Safety: `start` is within the slice, which is kept alive by
+                // `shared`.
+                let ptr = unsafe { base.add(*start) };
+                *start += 1;
+                Some(Supercow::shared_nocvt(shared.clone(), ptr))
+            },
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.inner {
+            IntoIterImpl::Owned(ref it) => it.size_hint(),
+            IntoIterImpl::Borrowed(ref it) => it.size_hint(),
+            IntoIterImpl::Shared(_, _, start, end) =>
+                (end - start, Some(end - start)),
+        }
+    }
+}
+
+impl<'a, T : 'a, SHARED, STORAGE> DoubleEndedIterator
+for IntoIter<'a, T, SHARED, STORAGE>
+where SHARED : Clone,
+      STORAGE : OwnedStorage<T, SHARED> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        match self.inner {
+            IntoIterImpl::Owned(ref mut it) =>
+                it.next_back().map(Supercow::owned),
+            IntoIterImpl::Borrowed(ref mut it) =>
+                it.next_back().map(Supercow::borrowed),
+            IntoIterImpl::Shared(ref shared, base, start, ref mut end) => {
+                if start == *end {
+                    return None;
+                }
+
+                *end -= 1;
+                // Safety: `end` is within the slice, which is kept alive by
+                // `shared`.
+                let ptr = unsafe { base.add(*end) };
+                Some(Supercow::shared_nocvt(shared.clone(), ptr))
+            },
+        }
+    }
+}
+
+impl<'a, T : 'a, SHARED, STORAGE> ExactSizeIterator
+for IntoIter<'a, T, SHARED, STORAGE>
+where SHARED : Clone,
+      STORAGE : OwnedStorage<T, SHARED> { }
+
+/// Iterator over the lines of a `Supercow` borrowing `str`.
+///
+/// This is returned by `Supercow::lines()`. Lines are split the same way as
+/// `str::lines()`, and each line shares the storage of the original string.
+pub struct Lines<'a, OWNED, SHARED, STORAGE>
+where OWNED : SafeBorrow<str>,
+      SHARED : Clone + TwoStepShared<OWNED, str>,
+      STORAGE : OwnedStorage<OWNED, SHARED> {
+    // Never in owned mode.
+    base: Supercow<'a, OWNED, str, SHARED, STORAGE>,
+    pos: usize,
+}
+
+impl<'a, OWNED, SHARED, STORAGE> Supercow<'a, OWNED, str, SHARED, STORAGE>
+where OWNED : SafeBorrow<str>,
+      SHARED : Clone + TwoStepShared<OWNED, str>,
+      STORAGE : OwnedStorage<OWNED, SHARED> {
+    /// Returns an iterator over the lines of `this`, each of which is a
+    /// `Supercow` sharing the storage of `this`.
+    ///
+    /// As with `slice()`, if `this` is owned, it is first moved into shared
+    /// mode so that it can be shared by the lines.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use supercow::Supercow;
+    ///
+    /// let mut text: Supercow<String, str> =
+    ///   Supercow::owned("foo\r\nbar\nbaz".to_owned());
+    /// let lines: Vec<_> = Supercow::lines(&mut text).collect();
+    /// assert_eq!(lines, vec!["foo", "bar", "baz"]);
+    /// assert_eq!(text[5..].as_ptr(), lines[1].as_ptr());
+    /// ```
+    pub fn lines(this: &mut Self) -> Lines<'a, OWNED, SHARED, STORAGE> {
+        Lines {
+            base: Supercow::share(this),
+            pos: 0,
+        }
+    }
+}
+
+impl<'a, OWNED, SHARED, STORAGE> Iterator
+for Lines<'a, OWNED, SHARED, STORAGE>
+where OWNED : SafeBorrow<str>,
+      SHARED : Clone + TwoStepShared<OWNED, str>,
+      STORAGE : OwnedStorage<OWNED, SHARED> {
+    type Item = Supercow<'a, OWNED, str, SHARED, STORAGE>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let len = self.base.len();
+        if self.pos >= len {
+            return None;
+        }
+
+        let start = self.pos;
+        let end = match self.base[start..].find('\n') {
+            Some(nl) => {
+                self.pos = start + nl + 1;
+                // As with `str::lines()`, a `\r` is only part of the line
+                // terminator if it immediately precedes the `\n`.
+                if self.base[start..start + nl].ends_with('\r') {
+                    start + nl - 1
+                } else {
+                    start + nl
+                }
+            },
+            None => {
+                self.pos = len;
+                len
+            },
+        };
+
+        Some(Supercow::slice(&mut self.base, start..end))
+    }
+}
+
+/// Owning iterator over the `char`s of a `Supercow` borrowing `str`.
+///
+/// This is returned by `Supercow::into_chars()`. It holds on to the
+/// `Supercow` itself, so the string is never copied, and in borrowed mode the
+/// iterator is only bound by the lifetime of the borrow.
+pub struct IntoChars<'a, OWNED, SHARED, STORAGE>
+where OWNED : SafeBorrow<str>,
+      STORAGE : OwnedStorage<OWNED, SHARED> {
+    base: Supercow<'a, OWNED, str, SHARED, STORAGE>,
+    // Byte offsets of the yet-unvisited range.
+    start: usize,
+    end: usize,
+}
+
+impl<'a, OWNED, SHARED, STORAGE> Supercow<'a, OWNED, str, SHARED, STORAGE>
+where OWNED : SafeBorrow<str>,
+      STORAGE : OwnedStorage<OWNED, SHARED> {
+    /// Converts `this` into an iterator over its `char`s, preserving its
+    /// mode.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use supercow::Supercow;
+    ///
+    /// let text: Supercow<String, str> =
+    ///   Supercow::owned("h\u{e9}!".to_owned());
+    /// let chars: String = Supercow::into_chars(text).rev().collect();
+    /// assert_eq!("!\u{e9}h", chars);
+    /// ```
+    pub fn into_chars(this: Self) -> IntoChars<'a, OWNED, SHARED, STORAGE> {
+        let end = this.len();
+        IntoChars {
+            base: this,
+            start: 0,
+            end,
+        }
+    }
+}
+
+impl<'a, OWNED, SHARED, STORAGE> Iterator
+for IntoChars<'a, OWNED, SHARED, STORAGE>
+where OWNED : SafeBorrow<str>,
+      STORAGE : OwnedStorage<OWNED, SHARED> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.base[self.start..self.end].chars().next()?;
+        self.start += c.len_utf8();
+        Some(c)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let bytes = self.end - self.start;
+        (bytes.div_ceil(4), Some(bytes))
+    }
+}
+
+impl<'a, OWNED, SHARED, STORAGE> DoubleEndedIterator
+for IntoChars<'a, OWNED, SHARED, STORAGE>
+where OWNED : SafeBorrow<str>,
+      STORAGE : OwnedStorage<OWNED, SHARED> {
+    fn next_back(&mut self) -> Option<char> {
+        let c = self.base[self.start..self.end].chars().next_back()?;
+        self.end -= c.len_utf8();
+        Some(c)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::super::*;
+
+    struct NotCloneable(u32);
+
+    #[test]
+    fn owned_items_are_moved() {
+        let v: Supercow<Vec<NotCloneable>, [NotCloneable]> =
+            Supercow::owned(vec![NotCloneable(1), NotCloneable(2)]);
+        let items: Vec<Supercow<NotCloneable>> = v.into_iter().collect();
+
+        assert_eq!(2, items.len());
+        assert_eq!(1, items[0].0);
+        assert_eq!(2, items[1].0);
+        assert!(Supercow::clone_non_owned(&items[0]).is_none());
+    }
+
+    #[test]
+    fn borrowed_items_borrow() {
+        let v = vec![1u32, 2, 3];
+        let s: Supercow<Vec<u32>, [u32]> = Supercow::borrowed(&v);
+
+        let mut n = 0;
+        for (item, orig) in s.into_iter().zip(v.iter()) {
+            assert_eq!(Some(orig), Supercow::extract_ref(&item));
+            n += 1;
+        }
+        assert_eq!(3, n);
+    }
+
+    #[test]
+    fn shared_items_share() {
+        let v = Arc::new(vec![1u32, 2, 3]);
+        let s: Supercow<Vec<u32>, [u32]> =
+            Supercow::shared(TwoStepArcVec(v.clone()));
+        let items: Vec<Supercow<u32>> = s.into_iter().rev().collect();
+
+        assert_eq!(3, items.len());
+        for (item, orig) in items.iter().rev().zip(v.iter()) {
+            assert_eq!(orig as *const u32, &**item as *const u32);
+            assert_eq!(None, Supercow::extract_ref(item));
+        }
+        assert_eq!(4, Arc::strong_count(&v));
+        drop(items);
+        assert_eq!(1, Arc::strong_count(&v));
+    }
+
+    // Minimal shared reference type dereferencing a `Vec` to a slice.
+    #[derive(Clone)]
+    struct TwoStepArcVec(Arc<Vec<u32>>);
+    unsafe impl ConstDeref for TwoStepArcVec {
+        type Target = [u32];
+        fn const_deref(&self) -> &[u32] { &self.0 }
+    }
+
+    #[test]
+    fn iter_is_exact_size() {
+        let s: Supercow<Vec<u32>, [u32]> = Supercow::owned(vec![1, 2, 3]);
+        let mut it = s.into_iter();
+        assert_eq!(3, it.len());
+        it.next();
+        assert_eq!(2, it.len());
+    }
+
+    #[test]
+    fn lines_of_borrowed() {
+        let text = "a\nbb\r\n\nccc\n";
+        let mut s: Supercow<String, str> = Supercow::borrowed(text);
+        let lines: Vec<_> = Supercow::lines(&mut s).collect();
+
+        assert_eq!(lines, vec!["a", "bb", "", "ccc"]);
+        assert_eq!(lines, text.lines().collect::<Vec<_>>());
+        assert_eq!(Some(&text[2..4]), Supercow::extract_ref(&lines[1]));
+    }
+
+    #[test]
+    fn lines_of_owned() {
+        let mut s: Supercow<String, str> =
+            Supercow::owned("one\ntwo".to_owned());
+        let lines: Vec<_> = Supercow::lines(&mut s).collect();
+        drop(s);
+
+        assert_eq!(lines, vec!["one", "two"]);
+    }
+
+    #[test]
+    fn lines_keep_lone_carriage_returns() {
+        let text = "foo\rbar\r";
+        let mut s: Supercow<String, str> = Supercow::borrowed(text);
+        let lines: Vec<_> = Supercow::lines(&mut s).collect();
+
+        assert_eq!(lines, vec!["foo\rbar\r"]);
+        assert_eq!(lines, text.lines().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn chars_in_each_mode() {
+        let text = "a\u{e9}\u{1f404}z";
+        let expected: Vec<char> = text.chars().collect();
+
+        let s: Supercow<String, str> = Supercow::borrowed(text);
+        let it = Supercow::into_chars(s);
+        assert_eq!((2, Some(text.len())), it.size_hint());
+        assert_eq!(expected, it.collect::<Vec<_>>());
+
+        let s: Supercow<String, str> = Supercow::owned(text.to_owned());
+        let mut it = Supercow::into_chars(s);
+        assert_eq!(Some('a'), it.next());
+        assert_eq!(Some('z'), it.next_back());
+        assert_eq!(vec!['\u{e9}', '\u{1f404}'], it.collect::<Vec<_>>());
+
+        let mut owner: Supercow<String, str> =
+            Supercow::owned(text.to_owned());
+        let s = Supercow::share(&mut owner);
+        drop(owner);
+        assert_eq!(expected, Supercow::into_chars(s).collect::<Vec<_>>());
+    }
+}
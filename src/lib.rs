@@ -514,15 +514,26 @@
 //! The third type parameter type to `Supercow` specifies the shared reference
 //! type.
 //!
-//! The default is `Box<DefaultFeatures<'static>>`, which is a boxed trait
+//! The default is `Box<dyn DefaultFeatures<'static>>`, which is a boxed trait
 //! object describing the features a shared reference type must have while
 //! allowing any such reference to be used without needing a generic type
 //! argument.
 //!
 //! An alternate feature set can be found in `NonSyncFeatures`, which is also
 //! usable through the `NonSyncSupercow` typedef (which also makes it
-//! `'static`). You can create custom feature traits in this style with
-//! `supercow_features!`.
+//! `'static`). `SendFeatures`, `NoCloneFeatures`, and `DebugFeatures` are
+//! provided in the same way, through `SendSupercow`, `NoCloneSupercow`, and
+//! `DebugSupercow` respectively. You can create custom feature traits in this
+//! style with `supercow_features!`.
+//!
+//! If you only ever use `Arc` (or `Rc`) as shared references, `ArcSupercow`
+//! (or `RcSupercow`) uses the statically-dispatched `ext::ArcShared` (or
+//! `ext::RcShared`) instead, which avoids virtual calls and the `Box` around
+//! the shared reference itself. `BoxedStorage` still boxes the `ArcShared`
+//! value, though, so it is `InlineArcSupercow` (or `InlineRcSupercow`) which
+//! makes shared mode as cheap as a plain `Arc`.
+//! If you never use shared references at all, `LiteSupercow` uses the
+//! uninhabited `ext::NoShared`, ruling shared mode out entirely.
 //!
 //! It is perfectly legal to use a non-`'static` shared reference type. In
 //! fact, the original design for `Supercow<'a>` used `DefaultFeatures<'a>`.
@@ -553,7 +564,7 @@
 //!
 //! When in owned or shared mode, a `Supercow` needs someplace to store the
 //! `OWNED` or `SHARED` value itself. This can be customised with the fourth
-//! type parameter (`STORAGE`), and the `OwnedStorage` trait. Two strategies
+//! type parameter (`STORAGE`), and the `OwnedStorage` trait. Three strategies
 //! are provided by this crate:
 //!
 //! - `BoxedStorage` puts everything behind `Box`es. This has the advantage
@@ -562,10 +573,15 @@
 //! you pay for allocations on construction. This is the default with
 //! `Supercow` and `NonSyncSupercow`.
 //!
-//! - `InlineStorage` uses an `enum` to store the values inline in the
+//! - `InlineStorage` uses a `union` to store the values inline in the
 //! `Supercow`, thus incurring no allocation, but making the `Supercow` itself
 //! bigger. This is easily available via the `InlineSupercow` and
-//! `InlineNonSyncSupercow` types.
+//! `InlineNonSyncSupercow` types, as well as `InlineArcSupercow` and
+//! `InlineRcSupercow`, which also store their shared references inline.
+//!
+//! - `SmallStorage` stores values inline when they fit within a fixed number
+//! of bytes and boxes them otherwise, so the `Supercow` has a bounded size
+//! whatever `OWNED` is. This is available via `SmallSupercow`.
 //!
 //! If you find some need, you can define custom storage types, though note
 //! that the trait is quite unsafe and somewhat subtle.
@@ -596,6 +612,16 @@
 //! reference initialisation. Shared values still by default require one boxing
 //! level as well as virtual dispatch on certain operations; as described
 //! above, this property too can be dealt with by using a custom `SHARED` type.
+//! `InlineArcSupercow` and `InlineRcSupercow` do exactly that, so creating or
+//! cloning a shared instance costs only a reference count increment.
+//!
+//! Moving an owned value into shared mode with `Supercow::share()` always
+//! creates a two-step wrapper around the original `OWNED`, with the default
+//! `Supercow` as with every other type. For `String`, `Vec<T>` and similar
+//! owners, `InlineArcSupercow` additionally provides `Supercow::share_arc()`,
+//! which copies the borrowed data into a single `Arc<BORROWED>`, so sharing
+//! costs exactly one allocation and the data is reached without any extra
+//! indirection.
 //!
 //! ## Destruction Cost
 //!
@@ -701,11 +727,19 @@
 //!
 //! assert_eq!(size_of::<&'static str>() + size_of::<*const ()>() + dflag,
 //!            size_of::<Supercow<'static, String, str>>());
+//!
+//! // The internal mode pointer is never null, so `Option` is free.
+//! assert_eq!(size_of::<Supercow<'static, u32>>(),
+//!            size_of::<Option<Supercow<'static, u32>>>());
 //! ```
 //!
 //! Of course, you also pay for heap space in this case when using owned or
 //! shared `Supercow`s.
 //!
+//! If `BORROWED` is sized, `thin::ThinSupercow` packs the mode into the
+//! reference itself and so is exactly as big as a mundane reference, at the
+//! cost of an extra indirection when dereferencing owned or shared values.
+//!
 //! `InlineSupercow` can be quite large in comparison to a normal reference.
 //! You need to be particularly careful that structures you reference don't
 //! themselves contain `InlineSupercow`s or you can end up with
@@ -735,22 +769,33 @@
 //!         4 * mem::size_of::<Big>());
 //! ```
 //!
+//! `SmallSupercow` avoids this by only storing values inline up to a fixed
+//! size, so it is the better choice in generic code where `OWNED` may itself
+//! be large or contain further `Supercow`s.
+//!
 //! # Other Notes
 //!
 //! Using `Supercow` will not give your application `apt-get`-style Super Cow
 //! Powers.
 
 pub mod ext;
+pub mod interner;
+pub mod iter;
+pub mod thin;
 
+use std::any::Any;
 use std::borrow::Borrow;
 use std::cmp;
 use std::convert::AsRef;
+use std::error::Error;
 use std::fmt;
 use std::hash::{Hash, Hasher};
+use std::io;
+use std::iter::FromIterator;
 use std::marker::PhantomData;
 use std::mem;
-use std::ops::{Deref, DerefMut};
-use std::ptr;
+use std::ops::{self, Deref, DerefMut, Index, RangeFrom, RangeTo};
+use std::ptr::NonNull;
 use std::rc::Rc;
 use std::sync::Arc;
 
@@ -771,6 +816,10 @@ use self::ext::*;
 ///   pub trait FeatureName: SomeTrait, AnotherTrait);
 /// supercow_features!(
 ///   pub trait FeatureName2: SomeTrait, Clone, AnotherTrait);
+/// supercow_features!(
+///   pub trait FeatureName3: SomeTrait, Clone;
+///   pub type Name3Supercow, InlineName3Supercow,
+///            Name3Phantomcow, InlineName3Phantomcow);
 ///
 /// # fn main() { }
 /// ```
@@ -780,16 +829,46 @@ use self::ext::*;
 /// A public trait named `FeatureName` is defined which extends all the listed
 /// traits, minus special cases below.
 ///
+/// Each listed trait may be any path with generic arguments, such as
+/// `fmt::Debug` or `AsRef<[u8]>`, or a lifetime bound such as `'static`.
+///
 /// If `Clone` is listed, the trait gains a `clone_boxed()` method and
-/// `Box<FeatureName>` is `Clone`.
+/// `Box<FeatureName>` is `Clone`. `Clone` is recognised as such when written
+/// as `Clone`, `clone::Clone`, `std::clone::Clone` or `core::clone::Clone`
+/// (the latter two optionally with a leading `::`); any other path to it is
+/// treated as an ordinary trait and does not get this special handling.
 ///
 /// If `TwoStepShared(SomeType)` is listed, the boxed type will implement
 /// `TwoStepShared` for all `OWNED`/`BORROWED` pairs where
 /// `SomeType<OWNED,BORROWED>` implements the feature a whole and
-/// `OWNED: SafeBorrow<BORROWED>`.
+/// `OWNED: SafeBorrow<BORROWED>`. `SomeType` may be a path, and is usually
+/// `TwoStepArc`, `TwoStepRc`, or a wrapper declared with `supercow_twostep!`.
 ///
 /// All types which implement all the listed traits (including special cases)
 /// implement `FeatureName`.
+///
+/// If the trait list is followed by `; pub type` and four names, public type
+/// aliases with those names are defined which correspond to
+/// `NonSyncSupercow`, `InlineNonSyncSupercow`, `NonSyncPhantomcow` and
+/// `InlineNonSyncPhantomcow` respectively, but using `Box<dyn
+/// FeatureName<'static>>` as the `SHARED` type.
+///
+/// The generated code uses `dyn` for all trait objects, so the macro can be
+/// used from crates on any edition.
+///
+/// ```edition2021
+/// use std::sync::Arc;
+/// use supercow::{supercow_features, Supercow};
+/// use supercow::ext::TwoStepArc;
+///
+/// supercow_features!(
+///   pub trait SendFeatures: Clone, TwoStepShared(TwoStepArc), Send);
+///
+/// let a: Supercow<u32, u32, Box<dyn SendFeatures<'static>>> =
+///   Supercow::shared(Arc::new(42));
+/// let b = a.clone();
+/// assert_eq!(42, *b);
+/// ```
 
 // Historical note: Originally, the shared type was required to implement
 // `ConstDeref`, and so the shared type was `Box<$feature<Target = BORROWED>>`.
@@ -812,78 +891,192 @@ macro_rules! supercow_features {
     // The bracketed lists are:
     //
     // - Clone. Either empty or `[Clone clone_boxed]`. There needs to be
-    // useful tokens to match here so that we can "iterate" over them to
-    // conditionally generate related code.
+    //   useful tokens to match here so that we can "iterate" over them to
+    //   conditionally generate related code.
     //
     // - Two-step. Contains just the bare inner type. We "iterate" over the
-    // type to conditionally generate the related code.
+    //   type to conditionally generate the related code.
     //
-    // - Everything else. A comma-separated list of identifiers.
+    // - Everything else. A list of bounds, each wrapped in its own brackets
+    //   since a bound may be several tokens long (e.g., `fmt::Debug` or
+    //   `AsRef<[u8]>`).
     ($(#[$meta:meta])* pub trait $feature_name:ident: $($stuff:tt)*) => {
-        supercow_features!(@_ACCUM $(#[$meta])* pub trait $feature_name:
-                           [] [] [] $($stuff)*);
+        $crate::supercow_features!(
+            @_ACCUM $(#[$meta])* pub trait $feature_name:
+            [] [] [] $($stuff)*);
     };
 
-    // Special case for Clone
+    // Special case for Clone, including the usual ways of spelling out its
+    // path. These must come before the general path case below, which would
+    // otherwise accept them as ordinary traits.
+    (@_ACCUM $(#[$meta:meta])* pub trait $feature_name:ident:
+     $clone:tt $twostep:tt [$($others:tt)*] Clone $($more:tt)*) => {
+        $crate::supercow_features!(
+            @_ACCUM $(#[$meta])* pub trait $feature_name:
+            [Clone clone_boxed] $twostep [$($others)*]
+            $($more)*);
+    };
     (@_ACCUM $(#[$meta:meta])* pub trait $feature_name:ident:
-     $clone:tt $twostep:tt [$($others:tt),*] Clone $($more:tt)*) => {
-        supercow_features!(@_ACCUM $(#[$meta])* pub trait $feature_name:
-                           [Clone clone_boxed] $twostep [$($others)*]
-                           $($more)*);
+     $clone:tt $twostep:tt [$($others:tt)*]
+     $(::)? std::clone::Clone $($more:tt)*) => {
+        $crate::supercow_features!(
+            @_ACCUM $(#[$meta])* pub trait $feature_name:
+            $clone $twostep [$($others)*] Clone $($more)*);
+    };
+    (@_ACCUM $(#[$meta:meta])* pub trait $feature_name:ident:
+     $clone:tt $twostep:tt [$($others:tt)*]
+     $(::)? core::clone::Clone $($more:tt)*) => {
+        $crate::supercow_features!(
+            @_ACCUM $(#[$meta])* pub trait $feature_name:
+            $clone $twostep [$($others)*] Clone $($more)*);
+    };
+    (@_ACCUM $(#[$meta:meta])* pub trait $feature_name:ident:
+     $clone:tt $twostep:tt [$($others:tt)*]
+     clone::Clone $($more:tt)*) => {
+        $crate::supercow_features!(
+            @_ACCUM $(#[$meta])* pub trait $feature_name:
+            $clone $twostep [$($others)*] Clone $($more)*);
     };
 
     // Special case for Two-Step
     (@_ACCUM $(#[$meta:meta])* pub trait $feature_name:ident:
-     $clone:tt $twostep:tt [$($others:tt),*]
+     $clone:tt $twostep:tt [$($others:tt)*]
      TwoStepShared($($inner:tt)*)
      $($more:tt)*) => {
-        supercow_features!(@_ACCUM $(#[$meta])* pub trait $feature_name:
-                           $clone [$($inner)*] [$($others)*]
-                           $($more)*);
+        $crate::supercow_features!(
+            @_ACCUM $(#[$meta])* pub trait $feature_name:
+            $clone [[$($inner)*]] [$($others)*]
+            $($more)*);
     };
 
-    // Since we match token-trees instead of identifiers or similar, we get
-    // comma as a bare token. Simply throw it away.
-    //
-    // This does mean people can invoke the macro without the commata, though
-    // we don't officially support it. It would be possible to adjust the macro
-    // to reject invocations missing commas, but there the error would not be
-    // particularly clear, so for now just be tolerant.
+    // The special cases above leave the following comma (if any) at the head
+    // of the list. Simply throw it away.
+    (@_ACCUM $(#[$meta:meta])* pub trait $feature_name:ident:
+     $clone:tt $twostep:tt [$($others:tt)*], $($more:tt)*) => {
+        $crate::supercow_features!(
+            @_ACCUM $(#[$meta])* pub trait $feature_name:
+            $clone $twostep [$($others)*] $($more)*);
+    };
+
+    // Lifetime bounds, e.g. `'static`.
     (@_ACCUM $(#[$meta:meta])* pub trait $feature_name:ident:
-     $clone:tt $twostep:tt [$($others:tt),*], $($more:tt)*) => {
-        supercow_features!(@_ACCUM $(#[$meta])* pub trait $feature_name:
-                           $clone $twostep [$($others)*] $($more)*);
+     $clone:tt $twostep:tt [$($others:tt)*]
+     $other:lifetime $($more:tt)*) => {
+        $crate::supercow_features!(
+            @_ACCUM $(#[$meta])* pub trait $feature_name:
+            $clone $twostep [$($others)* [$other]]
+            $($more)*);
     };
 
-    // General case for non-special traits.
+    // General case for non-special traits, which may be arbitrary paths with
+    // generic arguments. A `path` fragment may only be followed by certain
+    // tokens, so we need separate cases for whether this is the last item.
+    (@_ACCUM $(#[$meta:meta])* pub trait $feature_name:ident:
+     $clone:tt $twostep:tt [$($others:tt)*]
+     $other:path, $($more:tt)*) => {
+        $crate::supercow_features!(
+            @_ACCUM $(#[$meta])* pub trait $feature_name:
+            $clone $twostep [$($others)* [$other]]
+            $($more)*);
+    };
+    (@_ACCUM $(#[$meta:meta])* pub trait $feature_name:ident:
+     $clone:tt $twostep:tt [$($others:tt)*]
+     $other:path; $($more:tt)*) => {
+        $crate::supercow_features!(
+            @_ACCUM $(#[$meta])* pub trait $feature_name:
+            $clone $twostep [$($others)* [$other]]; $($more)*);
+    };
+    (@_ACCUM $(#[$meta:meta])* pub trait $feature_name:ident:
+     $clone:tt $twostep:tt [$($others:tt)*] $other:path) => {
+        $crate::supercow_features!(
+            @_ACCUM $(#[$meta])* pub trait $feature_name:
+            $clone $twostep [$($others)* [$other]]);
+    };
+
+    // A bare identifier not followed by a comma. The path cases above cannot
+    // tell where such an item ends, so this keeps working as it always has
+    // for invocations which leave out the commata between plain trait names.
+    //
+    // We don't officially support that. It would be possible to adjust the
+    // macro to reject invocations missing commas, but there the error would
+    // not be particularly clear, so for now just be tolerant.
     (@_ACCUM $(#[$meta:meta])* pub trait $feature_name:ident:
-     $clone:tt $twostep:tt [$($others:ident),*] $other:ident $($more:tt)*) => {
-        supercow_features!(@_ACCUM $(#[$meta])* pub trait $feature_name:
-                           $clone $twostep [$($others, )* $other]
-                           $($more)*);
+     $clone:tt $twostep:tt [$($others:tt)*]
+     $other:ident $($more:tt)*) => {
+        $crate::supercow_features!(
+            @_ACCUM $(#[$meta])* pub trait $feature_name:
+            $clone $twostep [$($others)* [$other]]
+            $($more)*);
     };
 
     // Once there's no unexamined items left, we can actually fall through to
-    // defining stuff.
+    // defining stuff, along with the type aliases if they were requested.
     (@_ACCUM $(#[$meta:meta])* pub trait $feature_name:ident:
-     $clone:tt $twostep:tt [$($others:ident),*]) => {
-        supercow_features!(@_DEFINE $(#[$meta])* pub trait $feature_name:
-                           $clone $twostep [$($others),*]);
+     $clone:tt $twostep:tt [$($others:tt)*]
+     ; pub type $supercow:ident, $inline_supercow:ident,
+     $phantomcow:ident, $inline_phantomcow:ident $(;)?) => {
+        $crate::supercow_features!(
+            @_DEFINE $(#[$meta])* pub trait $feature_name:
+            $clone $twostep [$($others)*]);
+        $crate::supercow_features!(
+            @_ALIASES $feature_name: $supercow, $inline_supercow,
+            $phantomcow, $inline_phantomcow);
+    };
+    (@_ACCUM $(#[$meta:meta])* pub trait $feature_name:ident:
+     $clone:tt $twostep:tt [$($others:tt)*] $(;)?) => {
+        $crate::supercow_features!(
+            @_DEFINE $(#[$meta])* pub trait $feature_name:
+            $clone $twostep [$($others)*]);
+    };
+
+    (@_ALIASES $feature_name:ident: $supercow:ident, $inline_supercow:ident,
+     $phantomcow:ident, $inline_phantomcow:ident) => {
+        #[doc = concat!("`Supercow` with the default `SHARED` changed to `",
+                        stringify!($feature_name), "`.")]
+        pub type $supercow<'a, OWNED, BORROWED = OWNED> =
+            $crate::Supercow<'a, OWNED, BORROWED,
+                             Box<dyn $feature_name<'static> + 'static>,
+                             $crate::ext::BoxedStorage>;
+
+        #[doc = concat!("`", stringify!($supercow),
+                        "` with the `STORAGE` changed to `InlineStorage`.")]
+        pub type $inline_supercow<'a, OWNED, BORROWED = OWNED> =
+            $crate::Supercow<'a, OWNED, BORROWED,
+                             Box<dyn $feature_name<'static> + 'static>,
+                             $crate::ext::InlineStorage<
+                                 OWNED,
+                                 Box<dyn $feature_name<'static> + 'static>>>;
+
+        #[doc = concat!("The `Phantomcow` variant corresponding to `",
+                        stringify!($supercow), "`.")]
+        pub type $phantomcow<'a, OWNED, BORROWED = OWNED> =
+            $crate::Phantomcow<'a, OWNED, BORROWED,
+                               Box<dyn $feature_name<'static> + 'static>,
+                               $crate::ext::BoxedStorage>;
+
+        #[doc = concat!("The `Phantomcow` variant corresponding to `",
+                        stringify!($inline_supercow), "`.")]
+        pub type $inline_phantomcow<'a, OWNED, BORROWED = OWNED> =
+            $crate::Phantomcow<'a, OWNED, BORROWED,
+                               Box<dyn $feature_name<'static> + 'static>,
+                               $crate::ext::InlineStorage<
+                                   OWNED,
+                                   Box<dyn $feature_name<'static> + 'static>>>;
     };
 
     (@_DEFINE $(#[$meta:meta])*
      pub trait $feature_name:ident:
      [$($clone:ident $clone_boxed:ident)*]
-     [$($twostep_inner:ident)*]
-     [$($req:ident),*]) => {
+     [$([$($twostep_inner:tt)*])*]
+     [$([$($req:tt)*])*]) => {
         $(#[$meta])*
-        pub trait $feature_name<'a>: $($req +)* 'a {
+        pub trait $feature_name<'a>: $($($req)* +)* 'a {
             // NB "Iterate" over the clone section to conditionally generate
             // this code.
             $(
             /// Clone this value, and then immediately put it into a `Box`
             /// behind a trait object of this trait.
-            fn $clone_boxed(&self) -> Box<$feature_name<'a> + 'a>;
+            fn $clone_boxed(&self) -> Box<dyn $feature_name<'a> + 'a>;
             )*
 
             /// Returns the address of `self`.
@@ -892,10 +1085,10 @@ macro_rules! supercow_features {
             /// resorting to transmuting or the unstable `TraitObject` type.
             fn self_address_mut(&mut self) -> *mut ();
         }
-        impl<'a, T : 'a + $($req +)* $($clone +)* Sized>
+        impl<'a, T : 'a + $($($req)* +)* $($clone +)* Sized>
         $feature_name<'a> for T {
             $(
-            fn $clone_boxed(&self) -> Box<$feature_name<'a> + 'a> {
+            fn $clone_boxed(&self) -> Box<dyn $feature_name<'a> + 'a> {
                 let cloned: T = self.clone();
                 Box::new(cloned)
             }
@@ -908,13 +1101,13 @@ macro_rules! supercow_features {
         // This implementation is safe -- all we do is move `T`, so if `T` is
         // `ConstDeref`, its returned address will not be affected.
         unsafe impl<'a, T : $feature_name<'a>> $crate::ext::SharedFrom<T>
-        for Box<$feature_name<'a> + 'a> {
+        for Box<dyn $feature_name<'a> + 'a> {
             fn shared_from(t: T) -> Self {
                 Box::new(t)
             }
         }
         $(
-        impl<'a> $clone for Box<$feature_name<'a> + 'a> {
+        impl<'a> $clone for Box<dyn $feature_name<'a> + 'a> {
             fn clone(&self) -> Self {
                 $feature_name::clone_boxed(&**self)
             }
@@ -922,30 +1115,296 @@ macro_rules! supercow_features {
         )*
         $(
         impl<'a, S : 'a + ?Sized, T : 'a> $crate::ext::TwoStepShared<T, S>
-        for Box<$feature_name<'a> + 'a>
+        for Box<dyn $feature_name<'a> + 'a>
         where T : $crate::ext::SafeBorrow<S>,
-              $twostep_inner<T,S> : $feature_name<'a> {
+              $($twostep_inner)*<T,S> : $feature_name<'a> {
             fn new_two_step() -> Self {
                 Box::new(
-                    <$twostep_inner<T,S> as $crate::ext::TwoStepShared<T, S>>::
+                    <$($twostep_inner)*<T,S>
+                     as $crate::ext::TwoStepShared<T, S>>::
                     new_two_step())
             }
 
             unsafe fn deref_holder(&mut self) -> &mut Option<T> {
-                <$twostep_inner<T,S> as $crate::ext::TwoStepShared<T, S>>::
+                <$($twostep_inner)*<T,S>
+                 as $crate::ext::TwoStepShared<T, S>>::
                 deref_holder(
                     // Unsafe downcast from $feature_name to the declared
                     // two-step type. This is safe since the contract of
                     // `deref_holder()` guarantees that this value was
                     // constructed by `new_two_step()`.
                     &mut* ($feature_name::self_address_mut(&mut **self)
-                           as *mut $twostep_inner<T,S>))
+                           as *mut $($twostep_inner)*<T,S>))
             }
         }
         )*
     };
 }
 
+/// Forwards a trait through `Supercow` to the borrowed value.
+///
+/// ## Syntax
+///
+/// ```
+/// #[macro_use] extern crate supercow;
+///
+/// use supercow::Supercow;
+///
+/// // Define a new trait, and implement it for every `Supercow` whose
+/// // `BORROWED` implements it.
+/// supercow_delegate! {
+///   /// Some documentation, etc, if desired.
+///   pub trait Shape {
+///     fn area(&self) -> f64;
+///     fn describe(&self, prefix: &str) -> String;
+///   }
+/// }
+///
+/// // Implement an existing trait, defined elsewhere, for every `Supercow`
+/// // whose `BORROWED` implements it.
+/// pub trait Named { fn name(&self) -> String; }
+/// supercow_delegate! {
+///   impl Named {
+///     fn name(&self) -> String;
+///   }
+/// }
+///
+/// struct Square(f64);
+/// impl Shape for Square {
+///   fn area(&self) -> f64 { self.0 * self.0 }
+///   fn describe(&self, prefix: &str) -> String {
+///     format!("{}square", prefix)
+///   }
+/// }
+///
+/// fn total_area<T : Shape>(shapes: &[T]) -> f64 {
+///   shapes.iter().map(Shape::area).sum()
+/// }
+///
+/// # fn main() {
+/// let square = Square(2.0);
+/// let shapes: Vec<Supercow<Square>> = vec![
+///   Supercow::borrowed(&square), Supercow::owned(Square(3.0))];
+/// assert_eq!(13.0, total_area(&shapes));
+/// assert_eq!("a square", shapes[0].describe("a "));
+/// # }
+/// ```
+///
+/// ## Semantics
+///
+/// With the `trait` form, the trait is defined exactly as written. Both forms
+/// then implement the trait for `Supercow<'a, OWNED, BORROWED, SHARED,
+/// STORAGE>` whenever `BORROWED` implements it, with each method simply
+/// calling the same method on the dereferenced value.
+///
+/// Only methods taking `&self` and without type parameters are supported. The
+/// trait itself must not have type parameters, associated items, or
+/// supertraits.
+#[macro_export]
+macro_rules! supercow_delegate {
+    ($(#[$meta:meta])* $vis:vis trait $name:ident {
+        $($(#[$mmeta:meta])*
+          fn $meth:ident(&self $(, $arg:ident : $argty:ty)* $(,)?)
+              $(-> $ret:ty)?;)*
+    }) => {
+        $(#[$meta])*
+        $vis trait $name {
+            $($(#[$mmeta])*
+              fn $meth(&self $(, $arg : $argty)*) $(-> $ret)?;)*
+        }
+
+        $crate::supercow_delegate! {
+            impl $name {
+                $(fn $meth(&self $(, $arg : $argty)*) $(-> $ret)?;)*
+            }
+        }
+    };
+
+    (impl $tr:path {
+        $(fn $meth:ident(&self $(, $arg:ident : $argty:ty)* $(,)?)
+              $(-> $ret:ty)?;)*
+    }) => {
+        impl<'a, OWNED, BORROWED : ?Sized + $tr, SHARED, STORAGE, PTR>
+        $tr for $crate::Supercow<'a, OWNED, BORROWED, SHARED, STORAGE, PTR>
+        where BORROWED : 'a,
+              *const BORROWED : $crate::ext::PointerFirstRef,
+              STORAGE : $crate::ext::OwnedStorage<OWNED, SHARED>,
+              PTR : $crate::ext::PtrRead<BORROWED> {
+            $(fn $meth(&self $(, $arg : $argty)*) $(-> $ret)? {
+                <BORROWED as $tr>::$meth(&**self $(, $arg)*)
+            })*
+        }
+    };
+}
+
+/// Allows trait objects and custom dynamically-sized types to be used as
+/// `BORROWED`.
+///
+/// ## Syntax
+///
+/// ```
+/// #[macro_use] extern crate supercow;
+///
+/// use supercow::Supercow;
+///
+/// pub trait Handler { fn handle(&self, x: u32) -> u32; }
+///
+/// supercow_dst!(dyn Handler, dyn Handler + Send);
+///
+/// // Owned mode additionally needs `SafeBorrow`, which must give a static
+/// // replacement for the borrowed value.
+/// struct Nop;
+/// impl Handler for Nop { fn handle(&self, x: u32) -> u32 { x } }
+/// safe_borrow!(Box<dyn Handler> => dyn Handler, &Nop);
+///
+/// struct Double;
+/// impl Handler for Double { fn handle(&self, x: u32) -> u32 { x * 2 } }
+///
+/// # fn main() {
+/// let double = Double;
+/// let handlers: Vec<Supercow<Box<dyn Handler>, dyn Handler>> = vec![
+///   Supercow::borrowed(&double as &dyn Handler),
+///   Supercow::owned(Box::new(Nop) as Box<dyn Handler>),
+/// ];
+/// assert_eq!(42, handlers[1].handle(handlers[0].handle(21)));
+/// # }
+/// ```
+///
+/// ## Semantics
+///
+/// Implements `ext::PointerFirst` (and therefore makes `*const T`
+/// `PointerFirstRef`) for each listed type. Each type must be either a trait
+/// object or a struct whose last field is a slice, `str`, or trait object.
+///
+/// Generic DSTs cannot be declared with this macro; instead, implement
+/// `PointerFirst` for them directly.
+#[macro_export]
+macro_rules! supercow_dst {
+    ($($t:ty),* $(,)?) => {
+        $(unsafe impl $crate::ext::PointerFirst for $t { })*
+    };
+}
+
+/// Safely implements `ext::SafeBorrow` for user types.
+///
+/// ## Syntax
+///
+/// ```
+/// #[macro_use] extern crate supercow;
+///
+/// use std::borrow::Borrow;
+/// use supercow::Supercow;
+///
+/// pub struct Matrix { name: String, view: MatrixView }
+/// pub struct MatrixView { cells: [[f32; 2]; 2] }
+///
+/// impl Borrow<MatrixView> for Matrix {
+///   fn borrow(&self) -> &MatrixView { &self.view }
+/// }
+/// impl ToOwned for MatrixView {
+///   type Owned = Matrix;
+///   fn to_owned(&self) -> Matrix {
+///     Matrix { name: String::new(), view: MatrixView { cells: self.cells } }
+///   }
+/// }
+///
+/// static ZERO: MatrixView = MatrixView { cells: [[0.0; 2]; 2] };
+///
+/// safe_borrow!(Matrix => MatrixView, &ZERO);
+///
+/// # fn main() {
+/// let identity = Matrix {
+///   name: "I".to_owned(),
+///   view: MatrixView { cells: [[1.0, 0.0], [0.0, 1.0]] },
+/// };
+/// let mut s: Supercow<Matrix, MatrixView> = Supercow::borrowed(&identity);
+/// s.to_mut().view.cells[0][1] = 2.0;
+/// assert_eq!(2.0, s.cells[0][1]);
+/// assert_eq!(0.0, identity.view.cells[0][1]);
+/// # }
+/// ```
+///
+/// Several pairs can be given at once, separated by semicolons.
+///
+/// ## Semantics
+///
+/// `Owned => Borrowed, replacement` implements `SafeBorrow<Borrowed>` for
+/// `Owned`, using `replacement` (which must be a `&'static Borrowed`) as the
+/// return value of `borrow_replacement()`. `Owned` must already implement
+/// `Borrow<Borrowed>`.
+///
+/// Because the replacement never points into `Owned`, the only remaining
+/// requirement of `SafeBorrow` is that `borrow()` does not return a pointer
+/// more than `MAX_INTERNAL_BORROW_DISPLACEMENT` bytes into `Owned`. This is
+/// guaranteed by asserting at compile time that `Owned` is no larger than
+/// that. Larger types, as well as generic types, must implement `SafeBorrow`
+/// manually.
+///
+/// ```compile_fail
+/// #[macro_use] extern crate supercow;
+///
+/// use std::borrow::Borrow;
+///
+/// pub struct Huge { header: u32, body: [u8; 4096] }
+/// impl Borrow<u32> for Huge {
+///   fn borrow(&self) -> &u32 { &self.header }
+/// }
+///
+/// // Fails: `Huge` is larger than `MAX_INTERNAL_BORROW_DISPLACEMENT`.
+/// safe_borrow!(Huge => u32, &0);
+/// # fn main() { }
+/// ```
+///
+/// Note that `SafeBorrow` is already implemented for all types which borrow
+/// as themselves, slices, `str`, `CStr`, `OsStr`, `Path`, and `dyn Any`, so
+/// this macro is neither necessary nor usable in those cases.
+#[macro_export]
+macro_rules! safe_borrow {
+    ($($owned:ty => $borrowed:ty, $replacement:expr);* $(;)?) => { $(
+        const _: () = assert!(
+            ::std::mem::size_of::<$owned>() <=
+                $crate::ext::MAX_INTERNAL_BORROW_DISPLACEMENT,
+            "safe_borrow!() type too large for internal borrows");
+
+        unsafe impl $crate::ext::SafeBorrow<$borrowed> for $owned {
+            fn borrow_replacement<'a>(
+                _: $crate::ext::StaticRef<'a, $borrowed>)
+                -> $crate::ext::StaticRef<'a, $borrowed>
+            {
+                $replacement
+            }
+        }
+    )* };
+}
+
+/// Converts a `Supercow` into a `Supercow` of an unsized type, such as a
+/// trait object, preserving its mode.
+///
+/// `supercow_coerce!(s)` is shorthand for `Supercow::coerce(s, |b| b, |r| r)`;
+/// see `Supercow::coerce()` for details.
+///
+/// ## Example
+///
+/// ```
+/// #[macro_use] extern crate supercow;
+///
+/// use std::fmt::Debug;
+/// use supercow::Supercow;
+///
+/// # fn main() {
+/// let v = vec![1, 2, 3];
+/// let s: Supercow<Vec<i32>> = Supercow::borrowed(&v);
+/// let d: Supercow<Box<dyn Debug>, dyn Debug> = supercow_coerce!(s);
+/// assert_eq!("[1, 2, 3]", format!("{:?}", d));
+/// # }
+/// ```
+#[macro_export]
+macro_rules! supercow_coerce {
+    ($s:expr) => {
+        $crate::Supercow::coerce($s, |b| b, |r| r)
+    };
+}
+
 supercow_features!(
     /// The default shared reference type for `Supercow`.
     ///
@@ -962,6 +1421,49 @@ supercow_features!(
     /// Unlike `DefaultFeatures`, this only requires the shared reference type
     /// to be `Clone`, thus permitting `Rc`.
     pub trait NonSyncFeatures: Clone, TwoStepShared(TwoStepRc));
+supercow_features!(
+    /// The shared reference type for `SendSupercow`.
+    ///
+    /// This requires the shared reference type to be `Clone` and `Send`, but
+    /// not `Sync`. It is intended for `Supercow`s which get moved into other
+    /// threads but never referenced from more than one at a time, which
+    /// permits shared reference types that are `Send` but not `Sync`.
+    ///
+    /// Note that a `SendSupercow` is still only `Send` if `BORROWED` is
+    /// `Sync`, since in borrowed mode it is just a `&BORROWED`, and shared
+    /// values created by `Supercow::share()` live in a `TwoStepArc`. There is
+    /// no sound way around this: a borrowed `Supercow` of a `Cell` moved into
+    /// another thread would let both threads access the `Cell` at once. What
+    /// this does permit is `Send`-only `SHARED` types, such as handles which
+    /// keep per-handle state in a `Cell`.
+    pub trait SendFeatures: Clone, TwoStepShared(TwoStepArc), Send;
+    pub type SendSupercow, InlineSendSupercow,
+             SendPhantomcow, InlineSendPhantomcow);
+supercow_features!(
+    /// The shared reference type for `NoCloneSupercow`.
+    ///
+    /// This only requires the shared reference type to be `Send` and `Sync`,
+    /// so shared owners which cannot be cloned (for example, a handle to a
+    /// memory-mapped file) may be used. Consequently, neither `Clone` nor
+    /// `Supercow::share()` are available on `NoCloneSupercow`.
+    ///
+    /// Note that a plain `Box<T>` is not accepted as a shared owner, since
+    /// `Box<T>` is only `ConstDeref` when `T` itself is (i.e., a `Box` around
+    /// another shared reference type). To share a uniquely-owned value, wrap
+    /// the `Box` in a type which implements `ConstDeref` for it.
+    pub trait NoCloneFeatures: Send, Sync;
+    pub type NoCloneSupercow, InlineNoCloneSupercow,
+             NoClonePhantomcow, InlineNoClonePhantomcow);
+supercow_features!(
+    /// The shared reference type for `DebugSupercow`.
+    ///
+    /// This is `DefaultFeatures` with the addition of `fmt::Debug`, so that
+    /// the shared reference itself can be printed via
+    /// `Supercow::extract_shared()`.
+    pub trait DebugFeatures: Clone, TwoStepShared(TwoStepArc), Send, Sync,
+                             fmt::Debug;
+    pub type DebugSupercow, InlineDebugSupercow,
+             DebugPhantomcow, InlineDebugPhantomcow);
 
 /// `Supercow` with the default `SHARED` changed to `NonSyncFeatures`, enabling
 /// the use of `Rc` as a shared reference type as well as making it possible to
@@ -982,7 +1484,7 @@ supercow_features!(
 /// ```
 pub type NonSyncSupercow<'a, OWNED, BORROWED = OWNED> =
     Supercow<'a, OWNED, BORROWED,
-             Box<NonSyncFeatures<'static> + 'static>,
+             Box<dyn NonSyncFeatures<'static> + 'static>,
              BoxedStorage>;
 
 /// `Supercow` with the default `STORAGE` changed to `InlineStorage`.
@@ -991,8 +1493,11 @@ pub type NonSyncSupercow<'a, OWNED, BORROWED = OWNED> =
 /// shared `Supercow` (down to zero for owned, but note that the default
 /// `SHARED` still has its own `Box`) at the cost of bloating the `Supercow`
 /// itself, as it now needs to be able to fit a whole `OWNED` instance.
+///
+/// To also avoid the `Box` in shared mode, use `InlineArcSupercow` or
+/// `InlineRcSupercow`.
 pub type InlineSupercow<'a, OWNED, BORROWED = OWNED,
-                       SHARED = Box<DefaultFeatures<'static> + 'static>> =
+                       SHARED = Box<dyn DefaultFeatures<'static> + 'static>> =
     Supercow<'a, OWNED, BORROWED, SHARED, InlineStorage<OWNED, SHARED>>;
 
 /// `NonSyncSupercow` with the `STORAGE` changed to `InlineStorage`.
@@ -1000,8 +1505,148 @@ pub type InlineSupercow<'a, OWNED, BORROWED = OWNED,
 /// This combines both properties of `NonSyncSupercow` and `InlineSupercow`.
 pub type InlineNonSyncSupercow<'a, OWNED, BORROWED = OWNED> =
     Supercow<'a, OWNED, BORROWED,
-             Box<NonSyncFeatures<'static> + 'static>,
-             InlineStorage<OWNED, Box<NonSyncFeatures<'static> + 'static>>>;
+             Box<dyn NonSyncFeatures<'static> + 'static>,
+             InlineStorage<OWNED, Box<dyn NonSyncFeatures<'static> + 'static>>>;
+
+/// `Supercow` with `SHARED` changed to the statically-dispatched `ArcShared`.
+///
+/// This avoids the virtual calls of the default `SHARED` type, as well as the
+/// `Box` it puts around the shared reference. The trade-off is that only
+/// `Arc`s (and `TwoStepArc`s created by `Supercow::share()`) can be used as
+/// shared references.
+///
+/// Note that `BoxedStorage` still puts the `ArcShared` value itself in a
+/// `Box`, so creating or cloning a shared `ArcSupercow` still allocates once.
+/// Use `InlineArcSupercow` to make that a plain `Arc` clone.
+///
+/// ## Limitations
+///
+/// `ArcShared<OWNED, BORROWED>` is parameterised by the owned and borrowed
+/// types, since it may hold a `TwoStepArc<OWNED, BORROWED>`. Conversions which
+/// preserve the mode while changing those types, such as `Supercow::coerce()`,
+/// `Supercow::downcast()`, and iterating a `Supercow<Vec<T>, [T]>` by value,
+/// keep the original `SHARED` type. Their results are therefore not
+/// `ArcSupercow`s of the new types, but `Supercow`s whose `SHARED` is still
+/// the `ArcShared` of the old `OWNED` and `BORROWED`. These can be used and
+/// cloned as normal, but `Supercow::share()` is not available on them, since
+/// that `SHARED` cannot hold a two-step wrapper for the new types. The same
+/// applies to `RcSupercow` and the inline variants.
+///
+/// ## Example
+///
+/// ```
+/// use std::sync::Arc;
+///
+/// use supercow::{ArcSupercow, Supercow};
+///
+/// let a: ArcSupercow<String, str> = Supercow::shared(Arc::from("hello"));
+/// let b = a.clone();
+/// assert_eq!("hello", &*b);
+/// ```
+pub type ArcSupercow<'a, OWNED, BORROWED = OWNED> =
+    Supercow<'a, OWNED, BORROWED, ArcShared<OWNED, BORROWED>, BoxedStorage>;
+
+/// `ArcSupercow`, but using `Rc` and therefore neither `Send` nor `Sync`.
+pub type RcSupercow<'a, OWNED, BORROWED = OWNED> =
+    Supercow<'a, OWNED, BORROWED, RcShared<OWNED, BORROWED>, BoxedStorage>;
+
+/// `ArcSupercow` with the `STORAGE` changed to `InlineStorage`.
+///
+/// Since `ArcShared` is not boxed, the shared reference is stored directly in
+/// the `Supercow`, so neither owned nor shared mode need any allocation of
+/// their own. Cloning a shared `InlineArcSupercow` is just an `Arc` clone.
+///
+/// ## Example
+///
+/// ```
+/// use std::sync::Arc;
+///
+/// use supercow::{InlineArcSupercow, Supercow};
+///
+/// let a: InlineArcSupercow<u32> = Supercow::shared(Arc::new(42));
+/// let b = a.clone();
+/// assert_eq!(42, *b);
+/// ```
+pub type InlineArcSupercow<'a, OWNED, BORROWED = OWNED> =
+    Supercow<'a, OWNED, BORROWED, ArcShared<OWNED, BORROWED>,
+             InlineStorage<OWNED, ArcShared<OWNED, BORROWED>>>;
+
+/// `RcSupercow` with the `STORAGE` changed to `InlineStorage`.
+///
+/// See `InlineArcSupercow`.
+pub type InlineRcSupercow<'a, OWNED, BORROWED = OWNED> =
+    Supercow<'a, OWNED, BORROWED, RcShared<OWNED, BORROWED>,
+             InlineStorage<OWNED, RcShared<OWNED, BORROWED>>>;
+
+/// `Supercow` which can only be in owned or borrowed mode.
+///
+/// `SHARED` is the uninhabited `NoShared`, so shared mode is ruled out at
+/// compile time. This is effectively `std::borrow::Cow`, but without requiring
+/// `BORROWED : ToOwned`; cloning an instance is either a plain clone of the
+/// owned value or a copy of the reference.
+///
+/// ## Example
+///
+/// ```
+/// use supercow::{LiteSupercow, Supercow};
+///
+/// struct NotToOwned(u32);
+///
+/// let x = NotToOwned(42);
+/// let a: LiteSupercow<NotToOwned> = Supercow::borrowed(&x);
+/// let b: LiteSupercow<NotToOwned> = Supercow::owned(NotToOwned(56));
+/// assert_eq!(98, a.0 + b.0);
+/// ```
+///
+/// Shared references cannot be used:
+///
+/// ```compile_fail
+/// use std::sync::Arc;
+///
+/// use supercow::{LiteSupercow, Supercow};
+///
+/// let a: LiteSupercow<u32> = Supercow::shared(Arc::new(42));
+/// ```
+pub type LiteSupercow<'a, OWNED, BORROWED = OWNED> =
+    Supercow<'a, OWNED, BORROWED, NoShared, BoxedStorage>;
+
+/// `LiteSupercow` with the `STORAGE` changed to `InlineStorage`.
+///
+/// Since `NoShared` is uninhabited, the storage only needs room for `OWNED`.
+pub type InlineLiteSupercow<'a, OWNED, BORROWED = OWNED> =
+    Supercow<'a, OWNED, BORROWED, NoShared, InlineStorage<OWNED, NoShared>>;
+
+/// `Supercow` with the `STORAGE` changed to `SmallStorage`.
+///
+/// Owned and shared values of at most `N` bytes are stored inline without
+/// allocating, while larger ones are boxed. Since the size of the `Supercow`
+/// no longer depends on `OWNED`, this is the inline-ish variant to use in
+/// generic code, where `InlineSupercow` risks quadratic growth.
+///
+/// The default `N` fits four pointers, which in particular covers `String`,
+/// `Vec` and the default boxed `SHARED`.
+///
+/// ```
+/// use std::mem::size_of;
+///
+/// use supercow::{SmallSupercow, Supercow};
+///
+/// let small: SmallSupercow<u32> = Supercow::owned(42);
+/// assert_eq!(42, *small);
+///
+/// // Large types are boxed, so the `Supercow` stays the same size.
+/// let big: SmallSupercow<[u8; 4096]> = Supercow::owned([1u8; 4096]);
+/// assert_eq!(1, big[4095]);
+/// assert_eq!(size_of::<SmallSupercow<u32>>(),
+///            size_of::<SmallSupercow<[u8; 4096]>>());
+/// ```
+pub type SmallSupercow<'a, OWNED, BORROWED = OWNED,
+                       SHARED = Box<dyn DefaultFeatures<'static> + 'static>,
+                       const N: usize = DEFAULT_SMALL_STORAGE_SIZE> =
+    Supercow<'a, OWNED, BORROWED, SHARED, SmallStorage<OWNED, SHARED, N>>;
+
+/// The default inline capacity, in bytes, of `SmallSupercow`.
+pub const DEFAULT_SMALL_STORAGE_SIZE: usize = 4 * mem::size_of::<usize>();
 
 /// The actual generic reference type.
 ///
@@ -1019,7 +1664,7 @@ pub type InlineNonSyncSupercow<'a, OWNED, BORROWED = OWNED> =
 /// - `PTR : PtrRead<BORROWED>` means the operation is not available on
 /// `Phantomcow`.
 pub struct Supercow<'a, OWNED, BORROWED : ?Sized = OWNED,
-                    SHARED = Box<DefaultFeatures<'static> + 'static>,
+                    SHARED = Box<dyn DefaultFeatures<'static> + 'static>,
                     STORAGE = BoxedStorage, PTR = *const BORROWED>
 where BORROWED : 'a,
       *const BORROWED : PointerFirstRef,
@@ -1063,7 +1708,7 @@ where BORROWED : 'a,
     //
     // This has three states:
     //
-    // - Null. The `Supercow` holds a `&'a BORROWED`.
+    // - `BORROWED_MODE` (address 1). The `Supercow` holds a `&'a BORROWED`.
     //
     // - Even alignment. The `Supercow` holds an `OWNED` accessible via
     // `STORAGE` field a, and this value is what is passed into the `STORAGE`
@@ -1075,7 +1720,10 @@ where BORROWED : 'a,
     // end up with two levels of boxing here for `BoxedStorage`. This is
     // actually necessary so that the whole thing only takes one immediate
     // pointer.
-    mode: *mut (),
+    //
+    // None of these states are null, which gives `Option<Supercow>` a niche
+    // so that it is no larger than `Supercow`.
+    mode: NonNull<()>,
     storage: STORAGE,
 
     _owned: PhantomData<OWNED>,
@@ -1099,24 +1747,87 @@ where BORROWED : 'a,
 /// corresponding `Supercow` type minus the size of `&'a BORROWED`, though this
 /// may not be exact depending on `STORAGE` alignment, etc.
 pub type Phantomcow<'a, OWNED, BORROWED = OWNED,
-                    SHARED = Box<DefaultFeatures<'static> + 'static>,
+                    SHARED = Box<dyn DefaultFeatures<'static> + 'static>,
                     STORAGE = BoxedStorage> =
     Supercow<'a, OWNED, BORROWED, SHARED, STORAGE, ()>;
 
 /// The `Phantomcow` variant corresponding to `NonSyncSupercow`.
 pub type NonSyncPhantomcow<'a, OWNED, BORROWED = OWNED> =
-    Phantomcow<'a, OWNED, BORROWED, Box<NonSyncFeatures<'static> + 'static>,
+    Phantomcow<'a, OWNED, BORROWED, Box<dyn NonSyncFeatures<'static> + 'static>,
                BoxedStorage>;
 
 /// The `Phantomcow` variant corresponding to `InlineStorage`.
 pub type InlinePhantomcow<'a, OWNED, BORROWED = OWNED,
-                          SHARED = Box<DefaultFeatures<'static> + 'static>> =
+                        SHARED = Box<dyn DefaultFeatures<'static> + 'static>> =
     Phantomcow<'a, OWNED, BORROWED, SHARED, InlineStorage<OWNED, SHARED>>;
 
 /// The `Phantomcow` variant corresponding to `InlineNonSyncSupercow`.
 pub type InlineNonSyncPhantomcow<'a, OWNED, BORROWED = OWNED> =
-    Phantomcow<'a, OWNED, BORROWED, Box<NonSyncFeatures<'static> + 'static>,
-             InlineStorage<OWNED, Box<NonSyncFeatures<'static> + 'static>>>;
+    Phantomcow<'a, OWNED, BORROWED, Box<dyn NonSyncFeatures<'static> + 'static>,
+             InlineStorage<OWNED, Box<dyn NonSyncFeatures<'static> + 'static>>>;
+
+/// The `Phantomcow` variant corresponding to `ArcSupercow`.
+pub type ArcPhantomcow<'a, OWNED, BORROWED = OWNED> =
+    Phantomcow<'a, OWNED, BORROWED, ArcShared<OWNED, BORROWED>, BoxedStorage>;
+
+/// The `Phantomcow` variant corresponding to `RcSupercow`.
+pub type RcPhantomcow<'a, OWNED, BORROWED = OWNED> =
+    Phantomcow<'a, OWNED, BORROWED, RcShared<OWNED, BORROWED>, BoxedStorage>;
+
+/// The `Phantomcow` variant corresponding to `InlineArcSupercow`.
+pub type InlineArcPhantomcow<'a, OWNED, BORROWED = OWNED> =
+    Phantomcow<'a, OWNED, BORROWED, ArcShared<OWNED, BORROWED>,
+               InlineStorage<OWNED, ArcShared<OWNED, BORROWED>>>;
+
+/// The `Phantomcow` variant corresponding to `InlineRcSupercow`.
+pub type InlineRcPhantomcow<'a, OWNED, BORROWED = OWNED> =
+    Phantomcow<'a, OWNED, BORROWED, RcShared<OWNED, BORROWED>,
+               InlineStorage<OWNED, RcShared<OWNED, BORROWED>>>;
+
+/// The `Phantomcow` variant corresponding to `LiteSupercow`.
+pub type LitePhantomcow<'a, OWNED, BORROWED = OWNED> =
+    Phantomcow<'a, OWNED, BORROWED, NoShared, BoxedStorage>;
+
+/// The `Phantomcow` variant corresponding to `InlineLiteSupercow`.
+pub type InlineLitePhantomcow<'a, OWNED, BORROWED = OWNED> =
+    Phantomcow<'a, OWNED, BORROWED, NoShared, InlineStorage<OWNED, NoShared>>;
+
+/// The `Phantomcow` variant corresponding to `SmallSupercow`.
+pub type SmallPhantomcow<'a, OWNED, BORROWED = OWNED,
+                         SHARED = Box<dyn DefaultFeatures<'static> + 'static>,
+                         const N: usize = DEFAULT_SMALL_STORAGE_SIZE> =
+    Phantomcow<'a, OWNED, BORROWED, SHARED, SmallStorage<OWNED, SHARED, N>>;
+
+/// An `io::Read`, `io::BufRead`, and `io::Seek` implementation over the bytes
+/// of a `Supercow`.
+///
+/// This is simply an `io::Cursor` around the `Supercow`, which reads straight
+/// out of whatever the `Supercow` references regardless of its ownership mode,
+/// so no copy of the buffer is ever made.
+///
+/// The corresponding writing side is `io::Write`, which `Supercow` implements
+/// directly when `OWNED` does.
+///
+/// ## Example
+///
+/// ```
+/// use std::io::{BufRead, Cursor, Read};
+///
+/// use supercow::{Supercow, SupercowReader};
+///
+/// let data = b"hello\nworld\n".to_vec();
+/// let mut reader: SupercowReader = Cursor::new(Supercow::borrowed(&data));
+///
+/// let mut line = String::new();
+/// reader.read_line(&mut line).unwrap();
+/// assert_eq!("hello\n", line);
+/// // The remaining data is read directly out of `data`.
+/// assert_eq!(data[6..].as_ptr(), reader.fill_buf().unwrap().as_ptr());
+/// ```
+pub type SupercowReader<'a, OWNED = Vec<u8>,
+                        SHARED = Box<dyn DefaultFeatures<'static> + 'static>,
+                        STORAGE = BoxedStorage> =
+    io::Cursor<Supercow<'a, OWNED, [u8], SHARED, STORAGE>>;
 
 enum SupercowMode {
     Owned(*mut ()),
@@ -1125,17 +1836,26 @@ enum SupercowMode {
 }
 
 impl SupercowMode {
-    fn from_ptr(mode: *mut ()) -> Self {
-        if mode.is_null() {
+    fn from_ptr(mode: NonNull<()>) -> Self {
+        if mode == BORROWED_MODE {
             Borrowed
-        } else if mode.is_2_aligned() {
-            Owned(mode)
+        } else if mode.as_ptr().is_2_aligned() {
+            Owned(mode.as_ptr())
         } else {
-            Shared(mode.align2())
+            Shared(mode.as_ptr().align2())
         }
     }
 }
 
+// The `mode` value of a borrowed `Supercow`. Owned-mode values are non-null
+// and 2-aligned, and shared-mode values are those plus one, so neither can
+// ever be 1. (This is deliberately spelled out rather than using a "dangling"
+// pointer, since the exact value matters.)
+#[allow(unknown_lints, clippy::manual_dangling_ptr)]
+const BORROWED_MODE: NonNull<()> = unsafe {
+    NonNull::new_unchecked(1 as *mut ())
+};
+
 use self::SupercowMode::*;
 
 macro_rules! defimpl {
@@ -1183,10 +1903,18 @@ defimpl! {[] () where { } {
     /// This can create a `Supercow` with a `'static` lifetime.
     pub fn owned(inner: OWNED) -> Self
     where OWNED : SafeBorrow<BORROWED> {
-        // Safety: The invalid `ptr` does not escape; either the function sets
+        // The initial `ptr` points into `inner` on the stack, which becomes
+        // dangling once `inner` is moved into storage. It serves only to give
+        // `ptr` valid metadata (e.g., a vtable) until `borrow_owned()`
+        // overwrites it.
+        let ptr = PTR::new(inner.borrow());
+        // Safety: The dangling `ptr` does not escape; either the function sets
         // it properly, or panics and the value is destroyed.
-        let mut this = unsafe { Self::empty() };
-        this.mode = this.storage.allocate_a(inner);
+        let mut this = unsafe { Self::empty(ptr) };
+        // Safety: `allocate_a()` never returns null.
+        this.mode = unsafe {
+            NonNull::new_unchecked(this.storage.allocate_a(inner))
+        };
         // This line could panic, but the only thing that has not yet been
         // initialised properly is `ptr`, which is immaterial since the
         // `Supercow` will not escape this frame if this panics, and `Drop`
@@ -1200,13 +1928,9 @@ defimpl! {[] () where { } {
 
     /// Creates a new `Supercow` which borrows the given value.
     pub fn borrowed<T : Borrow<BORROWED> + ?Sized>(inner: &'a T) -> Self {
-        // Safety: The invalid `ptr` value will be overwritten before this
-        // function returns, and the value is destroyed on panic.
-        let mut this = unsafe { Self::empty() };
-        // No need to write to `mode`; `empty()` returns a borrowed-mode
-        // `Supercow`.
-        this.ptr.store_ptr(inner.borrow() as *const BORROWED);
-        this
+        // Safety: `ptr` is the borrowed value itself. No need to write to
+        // `mode`; `empty()` returns a borrowed-mode `Supercow`.
+        unsafe { Self::empty(PTR::new(inner.borrow())) }
     }
 
     /// Creates a new `Supercow` using the given shared reference.
@@ -1215,20 +1939,23 @@ defimpl! {[] () where { } {
     pub fn shared<T>(inner: T) -> Self
     where T : ConstDeref<Target = BORROWED>,
           SHARED : SharedFrom<T> {
-        let mut ptr = PTR::new();
-        ptr.store_ptr(inner.const_deref());
+        let ptr = PTR::new(inner.const_deref());
         Self::shared_nocvt(SHARED::shared_from(inner), ptr)
     }
 
     fn shared_nocvt(shared: SHARED, ptr: PTR) -> Self {
-        // Safety: The invalid `ptr` value will be overwritten before this
-        // function returns, and the value is destroyed on panic.
-        let mut this = unsafe { Self::empty() };
+        // Safety: `this` is put into shared mode before this function returns,
+        // and the value is destroyed on panic.
+        let mut this = unsafe { Self::empty(ptr) };
         // If something panics below, `ptr` is may become a dangling pointer.
         // That's fine, though, because the `Supercow` will not escape the
         // frame and `Drop` does not inspect `ptr`.
-        this.ptr = ptr;
-        this.mode = this.storage.allocate_b(shared).unalign2() as *mut ();
+        // Safety: `allocate_b()` never returns null, and `unalign2()` only sets
+        // the low bit.
+        this.mode = unsafe {
+            NonNull::new_unchecked(
+                this.storage.allocate_b(shared).unalign2() as *mut ())
+        };
         this
     }
 
@@ -1276,6 +2003,13 @@ defimpl! {[] () where { } {
     /// If this `Supercow` is in owned mode, the owned value is first moved
     /// into a new shared reference so that `OWNED` does not need to be cloned.
     ///
+    /// That shared reference is a two-step wrapper (a `TwoStepArc` for the
+    /// default `Supercow`), which holds the original `OWNED` and so reaches
+    /// the borrowed data through two allocations. This is the case for every
+    /// `Supercow` type, including the default one. For owners like `String`
+    /// or `Vec<T>`, `InlineArcSupercow` additionally offers `share_arc()`,
+    /// which instead copies the borrowed data into a single `Arc<BORROWED>`.
+    ///
     /// ## Example
     ///
     /// ```
@@ -1358,8 +2092,8 @@ defimpl! {[] () where { } {
                     //
                     // - `ptr` is a dangling pointer until we borrow the shared
                     // value below. Because of this, we can't eliminate the
-                    // `mode` case by setting it to null, since we don't have
-                    // anything `ptr` can legally point to.
+                    // `mode` case by setting it to borrowed mode, since we
+                    // don't have anything `ptr` can legally point to.
                     *holder = Some(unsafe {
                         // Safety: `mode` indicates we are in owned mode and so
                         // have storage a allocated.
@@ -1379,7 +2113,11 @@ defimpl! {[] () where { } {
                     }
                 };
                 this.storage = new_storage;
-                this.mode = shared_ptr.unalign2() as *mut ();
+                // Safety: `shared_ptr` came from `allocate_b()`, which never
+                // returns null, and `unalign2()` only sets the low bit.
+                this.mode = unsafe {
+                    NonNull::new_unchecked(shared_ptr.unalign2() as *mut ())
+                };
                 this.ptr.store_ptr(internal_ptr);
                 // End uninterrupted section
                 // `this.mode` now indicates shared mode, and `this.ptr` points
@@ -1447,6 +2185,33 @@ defimpl! {[] () where { } {
         }
     }
 
+    /// If `this` is shared, return a reference to the shared reference it
+    /// holds.
+    ///
+    /// Otherwise, return `None`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    ///
+    /// use supercow::{DebugSupercow, Supercow};
+    ///
+    /// let shared: DebugSupercow<u32> = Arc::new(42u32).into();
+    /// assert_eq!("Some(42)",
+    ///            format!("{:?}", Supercow::extract_shared(&shared)));
+    ///
+    /// let owned: DebugSupercow<u32> = 42u32.into();
+    /// assert!(Supercow::extract_shared(&owned).is_none());
+    /// ```
+    pub fn extract_shared(this: &Self) -> Option<&SHARED> {
+        match this.mode() {
+            // Safety: `mode` indicates we have storage b allocated.
+            Shared(s) => Some(unsafe { this.storage.get_ptr_b(s) }),
+            _ => None,
+        }
+    }
+
     /// Takes ownership of the underling value if needed, then returns it,
     /// consuming `self`.
     pub fn into_inner(mut this: Self) -> OWNED
@@ -1456,7 +2221,11 @@ defimpl! {[] () where { } {
         match this.mode() {
             Owned(ptr) => {
                 // Safety: `mode` indicates that storage a is allocated.
-                unsafe { this.storage.deallocate_into_a(ptr) }
+                let owned = unsafe { this.storage.deallocate_into_a(ptr) };
+                // Put `this` into borrowed mode so that dropping it does not
+                // free storage a a second time.
+                this.mode = BORROWED_MODE;
+                owned
             },
             _ => (*this).to_owned(),
         }
@@ -1495,7 +2264,9 @@ defimpl! {[] () where { } {
             // Safety: We know that `self` is now in owned mode and so has
             // storage a allocated. We also know that in owned mode,
             // `self.mode` is the exact pointer value that storage returned.
-            r: unsafe { self.storage.get_mut_a(self.mode) } as *mut OWNED,
+            r: unsafe {
+                self.storage.get_mut_a(self.mode.as_ptr())
+            } as *mut OWNED,
             parent: self,
         }
     }
@@ -1535,7 +2306,7 @@ defimpl! {[] () where { } {
                 ptr: this.ptr,
                 // mem::replace is critical for safety, otherwise we would
                 // double-free when `this` is dropped.
-                mode: mem::replace(&mut this.mode, ptr::null_mut()),
+                mode: mem::replace(&mut this.mode, BORROWED_MODE),
                 storage: mem::replace(&mut this.storage, new_storage),
                 _owned: PhantomData,
                 _borrowed: PhantomData,
@@ -1584,7 +2355,7 @@ defimpl! {[] () where { } {
                 ptr: this.ptr,
                 // mem::replace is critical for safety, otherwise we would
                 // double-free when `this` is dropped.
-                mode: mem::replace(&mut this.mode, ptr::null_mut()),
+                mode: mem::replace(&mut this.mode, BORROWED_MODE),
                 storage: mem::replace(&mut this.storage, new_storage),
                 _owned: PhantomData,
                 _borrowed: PhantomData,
@@ -1605,7 +2376,7 @@ defimpl! {[] () where { } {
             ptr: (),
             // mem::replace is critical for safety, otherwise we would
             // double-free when `this` is dropped.
-            mode: mem::replace(&mut this.mode, ptr::null_mut()),
+            mode: mem::replace(&mut this.mode, BORROWED_MODE),
             storage: mem::replace(&mut this.storage, new_storage),
             _owned: PhantomData,
             _borrowed: PhantomData,
@@ -1624,8 +2395,8 @@ defimpl! {[] () where { } {
     /// `self` must be in owned mode, and storage slot a allocated.
     unsafe fn borrow_owned(&mut self)
     where OWNED : SafeBorrow<BORROWED> {
-        let mut borrowed_ptr = self.storage.get_ptr_a(self.mode).borrow()
-            as *const BORROWED;
+        let mut borrowed_ptr = self.storage.get_ptr_a(self.mode.as_ptr())
+            .borrow() as *const BORROWED;
 
         // We have a strong assumption that nothing ever gets allocated below
         // MAX_INTERNAL_BORROW_DISPLACEMENT, so check that in debug mode. Note
@@ -1675,16 +2446,15 @@ defimpl! {[] () where { } {
         self.ptr.store_ptr(borrowed_ptr);
     }
 
-    /// Create an "empty" `Supercow`.
+    /// Create an "empty" `Supercow` in borrowed mode with the given `ptr`.
     ///
-    /// The value must not be exposed to the outside world as it has a null
-    /// `ptr`. However, it is safe to drop as-is as it is returned in reference
-    /// mode and has no uninitialised content as far as the compiler is
-    /// concerned.
-    unsafe fn empty() -> Self {
+    /// The value must not be exposed to the outside world unless `ptr` is
+    /// valid for `'a`. However, it is safe to drop as-is as it is returned in
+    /// reference mode.
+    unsafe fn empty(ptr: PTR) -> Self {
         Supercow {
-            ptr: PTR::new(),
-            mode: ptr::null_mut(),
+            ptr,
+            mode: BORROWED_MODE,
             storage: Default::default(),
             _owned: PhantomData,
             _borrowed: PhantomData,
@@ -1697,20 +2467,347 @@ defimpl! {[] () where { } {
     }
 } }
 
-defimpl! {[] (RefParent for) where {
-    OWNED : SafeBorrow<BORROWED>
+defimpl! {[] () where {
+    OWNED : SafeBorrow<BORROWED>,
+    SHARED : Clone + TwoStepShared<OWNED, BORROWED>,
+    PTR : PtrRead<BORROWED>,
 } {
-    type Owned = OWNED;
+    /// Returns a `Supercow` referencing the sub-slice `range` of `this`
+    /// without copying anything.
+    ///
+    /// If `this` is in owned mode, it is first moved into shared mode as with
+    /// `share()`, after which both it and the returned value point into the
+    /// same shared buffer. Otherwise, `this` is unaffected and the returned
+    /// value borrows or shares with it as appropriate.
+    ///
+    /// This works for any `BORROWED` which can be indexed by `range` to
+    /// produce another `BORROWED`, most notably `[T]` and `str`.
+    ///
+    /// ## Panics
+    ///
+    /// Panics under the same conditions as indexing `BORROWED` directly. In
+    /// particular, for `str` this means the ends of the range must lie on
+    /// `char` boundaries.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use supercow::Supercow;
+    ///
+    /// let mut frame: Supercow<Vec<u8>, [u8]> =
+    ///   Supercow::owned(vec![1, 2, 3, 4, 5]);
+    /// let body = Supercow::slice(&mut frame, 1..4);
+    ///
+    /// assert_eq!(&[2, 3, 4], &*body);
+    /// assert_eq!(frame[1..].as_ptr(), body.as_ptr());
+    /// ```
+    pub fn slice<R>(this: &mut Self, range: R) -> Self
+    where BORROWED : Index<R, Output = BORROWED> {
+        Self::project(this, |b| &b[range])
+    }
 
-    unsafe fn supercow_ref_drop(&mut self) {
-        // Safety: Contract guarantees we are in owned mode and that there are
-        // no live borrows of the owned value remaining.
-        self.borrow_owned()
+    /// Splits `this` into two `Supercow`s referencing `[0, mid)` and
+    /// `[mid, len)`, without copying anything.
+    ///
+    /// See `slice()` for details on how the storage is shared and when this
+    /// panics.
+    pub fn split_at(this: &mut Self, mid: usize) -> (Self, Self)
+    where BORROWED : Index<RangeTo<usize>, Output = BORROWED> +
+                     Index<RangeFrom<usize>, Output = BORROWED> {
+        (Self::slice(this, ..mid), Self::slice(this, mid..))
     }
-} }
 
-/// Provides mutable access to an owned value within a `Supercow`.
-///
+    /// Truncates `this` to `[0, at)` and returns a `Supercow` referencing
+    /// `[at, len)`, without copying anything.
+    ///
+    /// See `slice()` for details on how the storage is shared and when this
+    /// panics. If this panics, `this` is left referencing its full original
+    /// value, though it may have been moved into shared mode.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use supercow::Supercow;
+    ///
+    /// let mut line: Supercow<String, str> =
+    ///   Supercow::owned("key: value".to_owned());
+    /// let value = Supercow::split_off(&mut line, 5);
+    ///
+    /// assert_eq!("key: ", &*line);
+    /// assert_eq!("value", &*value);
+    /// ```
+    pub fn split_off(this: &mut Self, at: usize) -> Self
+    where BORROWED : Index<RangeTo<usize>, Output = BORROWED> +
+                     Index<RangeFrom<usize>, Output = BORROWED> {
+        let tail = Self::slice(this, at..);
+        let head = Self::slice(this, ..at);
+        *this = head;
+        tail
+    }
+
+    /// Returns a non-owned `Supercow` whose pointer is replaced by the result
+    /// of `f` applied to the current value.
+    ///
+    /// `this` is moved into shared mode first if it is owned, so that the
+    /// projected reference never points into storage owned by one particular
+    /// `Supercow`.
+    fn project<F>(this: &mut Self, f: F) -> Self
+    where F : FnOnce(&BORROWED) -> &BORROWED {
+        let mut ret = Self::share(this);
+        // `ret` is now borrowed or shared, so anything reachable from its
+        // target remains valid as long as `ret` itself.
+        let projected = f(&*ret) as *const BORROWED;
+        ret.ptr.store_ptr(projected);
+        ret
+    }
+} }
+
+defimpl! {[] () where {
+    PTR : PtrRead<BORROWED>,
+} {
+    /// Converts `this` into a `Supercow` of an unsized type, such as a trait
+    /// object, preserving its mode.
+    ///
+    /// A borrowed `Supercow` remains borrowed, with the reference converted by
+    /// `unsize_ref`. An owned `Supercow` moves its value into a new `Box`,
+    /// which is converted by `unsize_box`. A shared `Supercow` keeps the same
+    /// shared reference, with its pointer converted by `unsize_ref`.
+    ///
+    /// Both conversions are nearly always simply unsizing coercions, which
+    /// can be written as `|b| b` and `|r| r`. The `supercow_coerce!` macro
+    /// does exactly that.
+    ///
+    /// Note that the resulting `Supercow` can only be `to_mut()`ed if
+    /// `Box<NB>` implements `SafeBorrow<NB>`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use std::fmt::Display;
+    /// use supercow::Supercow;
+    ///
+    /// let forty_two = 42u32;
+    /// let items: Vec<Supercow<Box<dyn Display>, dyn Display>> = vec![
+    ///   Supercow::coerce(Supercow::<u32>::borrowed(&forty_two),
+    ///                    |b| b, |r| r),
+    ///   Supercow::coerce(Supercow::<String>::owned("foo".to_owned()),
+    ///                    |b| b, |r| r),
+    /// ];
+    ///
+    /// assert_eq!("42", items[0].to_string());
+    /// assert_eq!("foo", items[1].to_string());
+    /// ```
+    pub fn coerce<NB : ?Sized + 'a, NSTORAGE>(
+        mut this: Self,
+        unsize_box: fn (Box<OWNED>) -> Box<NB>,
+        unsize_ref: for<'x> fn (&'x BORROWED) -> &'x NB)
+        -> Supercow<'a, Box<NB>, NB, SHARED, NSTORAGE>
+    where *const NB : PointerFirstRef,
+          NSTORAGE : OwnedStorage<Box<NB>, SHARED> {
+        match this.mode() {
+            Owned(ptr) => {
+                // Safety: `mode` indicates that storage a is allocated. We
+                // put `this` into borrowed mode immediately after so that it
+                // does not try to free it again.
+                let owned = unsafe { this.storage.deallocate_into_a(ptr) };
+                this.mode = BORROWED_MODE;
+
+                let boxed = unsize_box(Box::new(owned));
+                // `boxed` always points to the heap, so unlike `owned()` there
+                // is no need to consider internal pointers here. This also
+                // means we don't need `Box<NB> : SafeBorrow<NB>`.
+                //
+                // Safety: `ret` is put into owned mode before it escapes.
+                let mut ret: Supercow<'a, Box<NB>, NB, SHARED, NSTORAGE> =
+                    unsafe {
+                        Supercow::empty(PtrWrite::new(&*boxed as *const NB))
+                    };
+                // Safety: `allocate_a()` never returns null.
+                ret.mode = unsafe {
+                    NonNull::new_unchecked(ret.storage.allocate_a(boxed))
+                };
+                ret
+            },
+
+            Borrowed => {
+                // Safety: In borrowed mode, `ptr` is valid for `'a`.
+                let borrowed: &'a BORROWED = unsafe { &*this.ptr.get_ptr() };
+                Supercow::borrowed(unsize_ref(borrowed))
+            },
+
+            Shared(ptr) => {
+                let target = unsize_ref(&*this) as *const NB;
+                // Safety: `mode` indicates that storage b is allocated. As
+                // above, `this` is immediately put into borrowed mode.
+                let shared = unsafe { this.storage.deallocate_into_b(ptr) };
+                this.mode = BORROWED_MODE;
+                // `target` remains valid since it points into the value held
+                // by `shared`, which does not move even if `shared` does.
+                Supercow::shared_nocvt(shared, PtrWrite::new(target))
+            },
+        }
+    }
+} }
+
+impl<'a, OWNED, BORROWED : ?Sized, PTR>
+Supercow<'a, OWNED, BORROWED, ArcShared<OWNED, BORROWED>,
+         InlineStorage<OWNED, ArcShared<OWNED, BORROWED>>, PTR>
+where BORROWED : 'a,
+      *const BORROWED : PointerFirstRef,
+      PTR : PtrWrite<BORROWED> + PtrRead<BORROWED> {
+    /// Like `share()`, but moves an owned value into a plain `Arc<BORROWED>`
+    /// instead of a two-step wrapper.
+    ///
+    /// This is intended for owners such as `String`, `Vec<T>`, and `PathBuf`
+    /// whose borrowed form can be stored directly in an `Arc<str>`,
+    /// `Arc<[T]>`, or `Arc<Path>`. The borrowed value is copied into a single
+    /// new allocation which is then reached without any further indirection,
+    /// and the original owner is freed. By contrast, `share()` keeps the
+    /// original owner alive inside a `TwoStepArc`, so that the data is behind
+    /// two allocations and two indirections.
+    ///
+    /// This is only available on `InlineArcSupercow`, since that is the only
+    /// configuration where it actually saves anything: with the default
+    /// `SHARED` type or `BoxedStorage`, boxing the shared reference costs
+    /// more allocations than the `Arc` saves, on top of the copy. On
+    /// `InlineArcSupercow`, the `Arc` is the only allocation made.
+    ///
+    /// If `this` is not owned, this behaves exactly like `share()`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use supercow::{InlineArcSupercow, Supercow};
+    ///
+    /// let mut first: InlineArcSupercow<String, str> =
+    ///   Supercow::owned("hello".to_owned());
+    /// let second = Supercow::share_arc(&mut first);
+    ///
+    /// assert_eq!("hello", &*first);
+    /// assert_eq!(first.as_ptr(), second.as_ptr());
+    /// assert!(Supercow::clone_non_owned(&first).is_some());
+    /// ```
+    pub fn share_arc(this: &mut Self) -> Self
+    where for<'x> Arc<BORROWED> : From<&'x BORROWED> {
+        match this.mode() {
+            Owned(_) => {
+                // Build the `Arc` before touching `this` so that nothing is
+                // left dangling if the conversion panics.
+                let arc: Arc<BORROWED> = Arc::from(&**this);
+                // Replacing `this` drops the original owned value.
+                *this = Self::shared(arc.clone());
+                Self::shared(arc)
+            },
+
+            Borrowed | Shared(_) => Self::clone_non_owned(this)
+                .expect("Non-owned Supercow not cloneable"),
+        }
+    }
+}
+
+impl<'a, ANY : ?Sized, SHARED, STORAGE>
+Supercow<'a, Box<ANY>, ANY, SHARED, STORAGE>
+where ANY : AnyDowncast + 'a,
+      *const ANY : PointerFirstRef,
+      STORAGE : OwnedStorage<Box<ANY>, SHARED> {
+    /// Attempts to convert `this` into a `Supercow` of the concrete type
+    /// `T`, preserving its mode.
+    ///
+    /// A borrowed `Supercow` remains borrowing the same value. An owned
+    /// `Supercow` has its box unpacked. A shared `Supercow` keeps the same
+    /// shared reference, pointing at the concrete value within it.
+    ///
+    /// If the value is not a `T`, `this` is returned unchanged as the
+    /// error.
+    ///
+    /// The result uses the same `STORAGE`, which must therefore be able to
+    /// hold a `T` as well. This is always the case for `BoxedStorage`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use std::any::Any;
+    /// use supercow::Supercow;
+    ///
+    /// let forty_two = 42u32;
+    /// let any: Supercow<Box<dyn Any>, dyn Any> =
+    ///   Supercow::borrowed(&forty_two as &dyn Any);
+    ///
+    /// let any = Supercow::downcast::<String>(any).unwrap_err();
+    /// let n: Supercow<u32> = Supercow::downcast(any).unwrap();
+    /// assert_eq!(42, *n);
+    /// ```
+    pub fn downcast<T : Any>(mut this: Self)
+                             -> Result<Supercow<'a, T, T, SHARED, STORAGE>,
+                                       Self>
+    where STORAGE : OwnedStorage<T, SHARED> {
+        if !ANY::is::<T>(&*this) {
+            return Err(this);
+        }
+
+        Ok(match this.mode() {
+            Owned(ptr) => {
+                // Safety: `mode` indicates that storage a is allocated.
+                // We put `this` into borrowed mode immediately after so
+                // that it does not try to free it again.
+                let owned = unsafe {
+                    <STORAGE as OwnedStorage<Box<ANY>, SHARED>>::
+                        deallocate_into_a(&mut this.storage, ptr)
+                };
+                this.mode = BORROWED_MODE;
+                let owned = ANY::downcast_box::<T>(owned).ok()
+                    .expect("`is()` and `downcast()` disagree");
+                <Supercow<'a, T, T, SHARED, STORAGE>>::owned(*owned)
+            },
+
+            Borrowed => {
+                // Safety: In borrowed mode, `ptr` is valid for `'a`.
+                let borrowed: &'a ANY = unsafe { &*this.ptr.get_ptr() };
+                <Supercow<'a, T, T, SHARED, STORAGE>>::borrowed(
+                    ANY::downcast_ref::<T>(borrowed)
+                        .expect("`is()` and `downcast_ref()` disagree"))
+            },
+
+            Shared(ptr) => {
+                let target = ANY::downcast_ref::<T>(&*this)
+                    .expect("`is()` and `downcast_ref()` disagree")
+                    as *const T;
+                // Safety: `mode` indicates that storage b is allocated.
+                // As above, `this` is immediately put into borrowed mode.
+                let shared = unsafe {
+                    <STORAGE as OwnedStorage<Box<ANY>, SHARED>>::
+                        deallocate_into_b(&mut this.storage, ptr)
+                };
+                this.mode = BORROWED_MODE;
+                // `target` points into the value held by `shared`, which
+                // does not move even if `shared` does.
+                <Supercow<'a, T, T, SHARED, STORAGE>>::shared_nocvt(
+                    shared, target)
+            },
+        })
+    }
+
+    /// Returns a reference to the value within `this` if it is a `T`, or
+    /// `None` otherwise.
+    pub fn downcast_ref<T : Any>(this: &Self) -> Option<&T> {
+        ANY::downcast_ref::<T>(&**this)
+    }
+}
+
+defimpl! {[] (RefParent for) where {
+    OWNED : SafeBorrow<BORROWED>
+} {
+    type Owned = OWNED;
+
+    unsafe fn supercow_ref_drop(&mut self) {
+        // Safety: Contract guarantees we are in owned mode and that there are
+        // no live borrows of the owned value remaining.
+        self.borrow_owned()
+    }
+} }
+
+/// Provides mutable access to an owned value within a `Supercow`.
+///
 /// This is similar to the `Ref` used with `RefCell`.
 pub struct Ref<'a, P>
 where P : RefParent + 'a {
@@ -1911,6 +3008,26 @@ where BORROWED : fmt::Debug + 'a,
     }
 }
 
+// `BORROWED` is implicitly `Sized` here so that this does not overlap with the
+// impl for `str` below.
+impl<'a, OWNED, BORROWED, SHARED, STORAGE>
+Error for Supercow<'a, OWNED, BORROWED, SHARED, STORAGE, *const BORROWED>
+where BORROWED : Error + 'a,
+      STORAGE : OwnedStorage<OWNED, SHARED> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        (**self).source()
+    }
+}
+
+/// A `Supercow` of a string is an error whose message is that string.
+///
+/// Together with the standard `From<E: Error>` conversions, this means that a
+/// `Supercow<'static, String, str>` can be returned through `?` from any
+/// function returning `Box<dyn Error>`.
+impl<'a, SHARED, STORAGE>
+Error for Supercow<'a, String, str, SHARED, STORAGE, *const str>
+where STORAGE : OwnedStorage<String, SHARED> { }
+
 defimpl! {[T] (cmp::PartialEq<T> for) where {
     T : Borrow<BORROWED>,
     BORROWED : PartialEq<BORROWED>,
@@ -1974,6 +3091,160 @@ defimpl! {[] (Hash for) where {
     }
 } }
 
+defimpl! {[] (Default for) where {
+    OWNED : Default + SafeBorrow<BORROWED>,
+} {
+    fn default() -> Self {
+        Self::owned(OWNED::default())
+    }
+} }
+
+defimpl! {[A] (FromIterator<A> for) where {
+    OWNED : FromIterator<A> + SafeBorrow<BORROWED>,
+} {
+    fn from_iter<I : IntoIterator<Item = A>>(iter: I) -> Self {
+        Self::owned(OWNED::from_iter(iter))
+    }
+} }
+
+// As with `io::Write` below, this only takes ownership if there is at least
+// one item to add.
+defimpl! {[A] (Extend<A> for) where {
+    OWNED : Extend<A> + SafeBorrow<BORROWED>,
+    BORROWED : ToOwned<Owned = OWNED>,
+    PTR : PtrRead<BORROWED>,
+} {
+    fn extend<I : IntoIterator<Item = A>>(&mut self, iter: I) {
+        let mut iter = iter.into_iter().peekable();
+        if iter.peek().is_some() {
+            self.to_mut().extend(iter);
+        }
+    }
+} }
+
+defimpl! {[] (fmt::Write for) where {
+    OWNED : fmt::Write + SafeBorrow<BORROWED>,
+    BORROWED : ToOwned<Owned = OWNED>,
+    PTR : PtrRead<BORROWED>,
+} {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if s.is_empty() {
+            return Ok(());
+        }
+
+        self.to_mut().write_str(s)
+    }
+
+    fn write_char(&mut self, c: char) -> fmt::Result {
+        self.to_mut().write_char(c)
+    }
+} }
+
+// Writing only takes ownership once there is actually something to write, so
+// that a borrowed buffer which is never appended to is never copied.
+defimpl! {[] (io::Write for) where {
+    OWNED : io::Write + SafeBorrow<BORROWED>,
+    BORROWED : ToOwned<Owned = OWNED>,
+    PTR : PtrRead<BORROWED>,
+} {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        self.to_mut().write(buf)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        if buf.is_empty() {
+            return Ok(());
+        }
+
+        self.to_mut().write_all(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.mode() {
+            Owned(_) => self.to_mut().flush(),
+            _ => Ok(()),
+        }
+    }
+} }
+
+defimpl! {[IDX] (ops::Index<IDX> for) where {
+    BORROWED : ops::Index<IDX>,
+    PTR : PtrRead<BORROWED>,
+} {
+    type Output = BORROWED::Output;
+
+    fn index(&self, index: IDX) -> &BORROWED::Output {
+        &(**self)[index]
+    }
+} }
+
+// Binary operators are implemented between references to `Supercow`s, just
+// as they would be between references to `BORROWED`. The assignment versions
+// take ownership via `to_mut()` and then apply the operator to `OWNED`.
+macro_rules! deleg_binop { ($tr:ident, $meth:ident,
+                            $atr:ident, $ameth:ident) => {
+    impl<'a, 'b, 'c, OWNED, BORROWED : ?Sized, SHARED, STORAGE, PTR>
+    ops::$tr<&'c Supercow<'a, OWNED, BORROWED, SHARED, STORAGE, PTR>>
+    for &'b Supercow<'a, OWNED, BORROWED, SHARED, STORAGE, PTR>
+    where BORROWED : 'a,
+          *const BORROWED : PointerFirstRef,
+          STORAGE : OwnedStorage<OWNED, SHARED>,
+          PTR : PtrRead<BORROWED>,
+          &'b BORROWED : ops::$tr<&'c BORROWED> {
+        type Output = <&'b BORROWED as ops::$tr<&'c BORROWED>>::Output;
+
+        fn $meth(self, rhs: &'c Supercow<'a, OWNED, BORROWED, SHARED,
+                                         STORAGE, PTR>)
+                 -> Self::Output {
+            ops::$tr::$meth(&**self, &**rhs)
+        }
+    }
+
+    defimpl! {[RHS] (ops::$atr<RHS> for) where {
+        OWNED : ops::$atr<RHS> + SafeBorrow<BORROWED>,
+        BORROWED : ToOwned<Owned = OWNED>,
+        PTR : PtrRead<BORROWED>,
+    } {
+        fn $ameth(&mut self, rhs: RHS) {
+            ops::$atr::$ameth(&mut *self.to_mut(), rhs)
+        }
+    } }
+} }
+
+deleg_binop!(Add, add, AddAssign, add_assign);
+deleg_binop!(BitAnd, bitand, BitAndAssign, bitand_assign);
+deleg_binop!(BitOr, bitor, BitOrAssign, bitor_assign);
+deleg_binop!(BitXor, bitxor, BitXorAssign, bitxor_assign);
+deleg_binop!(Div, div, DivAssign, div_assign);
+deleg_binop!(Mul, mul, MulAssign, mul_assign);
+deleg_binop!(Rem, rem, RemAssign, rem_assign);
+deleg_binop!(Shl, shl, ShlAssign, shl_assign);
+deleg_binop!(Shr, shr, ShrAssign, shr_assign);
+deleg_binop!(Sub, sub, SubAssign, sub_assign);
+
+macro_rules! deleg_unop { ($tr:ident, $meth:ident) => {
+    impl<'a, 'b, OWNED, BORROWED : ?Sized, SHARED, STORAGE, PTR>
+    ops::$tr for &'b Supercow<'a, OWNED, BORROWED, SHARED, STORAGE, PTR>
+    where BORROWED : 'a,
+          *const BORROWED : PointerFirstRef,
+          STORAGE : OwnedStorage<OWNED, SHARED>,
+          PTR : PtrRead<BORROWED>,
+          &'b BORROWED : ops::$tr {
+        type Output = <&'b BORROWED as ops::$tr>::Output;
+
+        fn $meth(self) -> Self::Output {
+            ops::$tr::$meth(&**self)
+        }
+    }
+} }
+
+deleg_unop!(Neg, neg);
+deleg_unop!(Not, not);
+
 trait ReferenceExt {
     fn address(&self) -> usize;
 }
@@ -2095,6 +3366,616 @@ mod misc_tests {
         assert_eq!(42, add_two_supercow(&Supercow::owned(40),
                                         &Supercow::owned(2)));
     }
+
+    supercow_delegate! {
+        trait Shout {
+            fn shout(&self) -> String;
+            fn shout_with(&self, suffix: &str, times: usize) -> String;
+        }
+    }
+
+    impl Shout for str {
+        fn shout(&self) -> String {
+            self.to_uppercase()
+        }
+
+        fn shout_with(&self, suffix: &str, times: usize) -> String {
+            self.shout() + &suffix.repeat(times)
+        }
+    }
+
+    trait Counted { fn count(&self) -> usize; }
+    impl<T> Counted for [T] {
+        fn count(&self) -> usize { self.len() }
+    }
+    supercow_delegate! {
+        impl self::Counted {
+            fn count(&self) -> usize;
+        }
+    }
+
+    fn shout_twice<T : Shout + ?Sized>(t: &T) -> String {
+        t.shout_with("!", 2)
+    }
+
+    #[test]
+    fn delegated_traits_work() {
+        let s: Supercow<String, str> = Supercow::borrowed("hello");
+        assert_eq!("HELLO", s.shout());
+        assert_eq!("HELLO!!", shout_twice(&s));
+
+        let s: NonSyncSupercow<String, str> =
+            Supercow::owned("world".to_owned());
+        assert_eq!("WORLD!!", shout_twice(&s));
+
+        let v: InlineSupercow<Vec<u8>, [u8]> = Supercow::owned(vec![1, 2, 3]);
+        assert_eq!(3, v.count());
+    }
+
+    trait Handler : Send + Sync {
+        fn handle(&self) -> u32;
+    }
+    supercow_dst!(dyn Handler);
+
+    struct Fixed(u32);
+    impl Handler for Fixed {
+        fn handle(&self) -> u32 { self.0 }
+    }
+    safe_borrow!(Box<dyn Handler> => dyn Handler, &Fixed(0));
+
+    struct Tagged<T : ?Sized> {
+        tag: u8,
+        data: T,
+    }
+    supercow_dst!(Tagged<[u32]>);
+
+    #[test]
+    fn trait_object_borrowed() {
+        let handler = Fixed(42);
+        let s: Supercow<Box<dyn Handler>, dyn Handler> =
+            Supercow::borrowed(&handler as &dyn Handler);
+        assert_eq!(42, s.handle());
+        let s2 = Supercow::clone_non_owned(&s).unwrap();
+        assert_eq!(42, s2.handle());
+    }
+
+    #[test]
+    fn trait_object_owned() {
+        let s: Supercow<Box<dyn Handler>, dyn Handler> =
+            Supercow::owned(Box::new(Fixed(42)));
+        assert_eq!(42, s.handle());
+
+        let s: InlineSupercow<Box<dyn Handler>, dyn Handler> =
+            Supercow::owned(Box::new(Fixed(56)));
+        let s = vec![s];
+        assert_eq!(56, s[0].handle());
+    }
+
+    #[test]
+    fn trait_object_shared() {
+        let s: Supercow<Box<dyn Handler>, dyn Handler> =
+            Supercow::shared(Arc::new(Fixed(42)) as Arc<dyn Handler>);
+        let s2 = Supercow::clone_non_owned(&s).unwrap();
+        assert_eq!(42, s.handle());
+        assert_eq!(42, s2.handle());
+    }
+
+    #[test]
+    fn any_borrowed_and_owned() {
+        use std::any::Any;
+
+        let x = 42u32;
+        let s: Supercow<Box<dyn Any>, dyn Any> =
+            Supercow::borrowed(&x as &dyn Any);
+        assert_eq!(Some(&42u32), s.downcast_ref::<u32>());
+
+        let s: Supercow<Box<dyn Any + Send>, dyn Any + Send> =
+            Supercow::owned(Box::new("foo") as Box<dyn Any + Send>);
+        assert_eq!(Some(&"foo"), s.downcast_ref::<&str>());
+    }
+
+    #[test]
+    fn custom_dst_borrowed() {
+        let tagged = Tagged { tag: 7, data: [1u32, 2, 3] };
+        let tagged: &Tagged<[u32]> = &tagged;
+        let s: Supercow<Box<Tagged<[u32]>>, Tagged<[u32]>> =
+            Supercow::borrowed(tagged);
+        assert_eq!(7, s.tag);
+        assert_eq!(&[1, 2, 3], &s.data);
+        assert_eq!(tagged as *const Tagged<[u32]>,
+                   &*s as *const Tagged<[u32]>);
+    }
+
+    #[test]
+    fn coerce_preserves_mode() {
+        let handler = Fixed(1);
+        let borrowed: Supercow<Fixed> = Supercow::borrowed(&handler);
+        let owned: Supercow<Fixed> = Supercow::owned(Fixed(2));
+        let shared: Supercow<Fixed> = Supercow::shared(Arc::new(Fixed(3)));
+
+        let handlers: Vec<Supercow<Box<dyn Handler>, dyn Handler>> = vec![
+            Supercow::coerce(borrowed, |b| b, |r| r),
+            supercow_coerce!(owned),
+            supercow_coerce!(shared),
+        ];
+
+        assert_eq!(vec![1, 2, 3],
+                   handlers.iter().map(|h| h.handle()).collect::<Vec<_>>());
+        assert_eq!(&handler as *const Fixed as *const (),
+                   &*handlers[0] as *const dyn Handler as *const ());
+        assert!(Supercow::extract_ref(&handlers[0]).is_some());
+        assert!(Supercow::clone_non_owned(&handlers[1]).is_none());
+        assert!(Supercow::extract_ref(&handlers[2]).is_none());
+        assert!(Supercow::clone_non_owned(&handlers[2]).is_some());
+    }
+
+    #[test]
+    fn coerce_inline() {
+        let owned: InlineSupercow<Fixed> = Supercow::owned(Fixed(2));
+        let shared: InlineSupercow<Fixed> =
+            Supercow::shared(Arc::new(Fixed(3)));
+
+        let handlers: Vec<InlineSupercow<Box<dyn Handler>, dyn Handler>> =
+            vec![supercow_coerce!(owned), supercow_coerce!(shared)];
+        assert_eq!(2, handlers[0].handle());
+        assert_eq!(3, handlers[1].handle());
+    }
+
+    #[test]
+    fn downcast_borrowed() {
+        use std::any::Any;
+
+        let x = 42u32;
+        let s: Supercow<Box<dyn Any>, dyn Any> =
+            Supercow::borrowed(&x as &dyn Any);
+        assert_eq!(None, Supercow::downcast_ref::<i32>(&s));
+        assert_eq!(Some(&42), Supercow::downcast_ref::<u32>(&s));
+
+        let s = Supercow::downcast::<i32>(s).unwrap_err();
+        let s = Supercow::downcast::<u32>(s).unwrap();
+        assert_eq!(&x as *const u32, &*s as *const u32);
+        assert!(Supercow::extract_ref(&s).is_some());
+    }
+
+    #[test]
+    fn downcast_owned() {
+        use std::any::Any;
+
+        let s: Supercow<Box<dyn Any + Send>, dyn Any + Send> =
+            Supercow::owned(Box::new("foo".to_owned()) as Box<dyn Any + Send>);
+        let s = Supercow::downcast::<&str>(s).unwrap_err();
+        let mut s = Supercow::downcast::<String>(s).unwrap();
+        assert_eq!("foo", *s);
+        s.to_mut().push_str("bar");
+        assert_eq!("foobar", Supercow::into_inner(s));
+    }
+
+    #[test]
+    fn downcast_shared() {
+        use std::any::Any;
+
+        let arc: Arc<dyn Any + Send + Sync> = Arc::new(vec![1u8, 2, 3]);
+        let s: Supercow<Box<dyn Any + Send + Sync>, dyn Any + Send + Sync> =
+            Supercow::shared(arc.clone());
+        let s = Supercow::downcast::<Vec<u8>>(s).unwrap();
+        assert_eq!(&[1, 2, 3], &s[..]);
+        assert_eq!(&*arc as *const _ as *const (),
+                   &*s as *const _ as *const ());
+        assert!(Supercow::clone_non_owned(&s).is_some());
+        drop(arc);
+        assert_eq!(&[1, 2, 3], &s[..]);
+    }
+
+    struct Labelled {
+        label: &'static str,
+        value: Value,
+    }
+    struct Value(u64);
+    impl Borrow<Value> for Labelled {
+        fn borrow(&self) -> &Value { &self.value }
+    }
+    impl ToOwned for Value {
+        type Owned = Labelled;
+        fn to_owned(&self) -> Labelled {
+            Labelled { label: "", value: Value(self.0) }
+        }
+    }
+    static ZERO: Value = Value(0);
+    safe_borrow!(Labelled => Value, &ZERO);
+
+    #[test]
+    fn safe_borrow_macro_internal_pointer() {
+        let mut s: InlineSupercow<Labelled, Value> =
+            Supercow::owned(Labelled { label: "x", value: Value(42) });
+        assert_eq!(42, s.0);
+        {
+            let mut r = s.to_mut();
+            assert_eq!("x", r.label);
+            r.value.0 += 1;
+        }
+        let s = [s];
+        assert_eq!(43, s[0].0);
+    }
+
+    supercow_features!(
+        pub trait PathFeatures: Clone, TwoStepShared(TwoStepArc),
+                                ::std::marker::Send, Sync, fmt::Debug,
+                                'static, AsRef<u32>);
+
+    supercow_features!(
+        pub trait ClonePathFeatures: ::std::clone::Clone Send Sync);
+
+    supercow_features!(
+        pub trait AliasedFeatures: Clone, TwoStepShared(TwoStepRc);
+        pub type AliasedSupercow, InlineAliasedSupercow,
+                 AliasedPhantomcow, InlineAliasedPhantomcow);
+    supercow_features!(
+        pub trait AliasedPathFeatures: Clone, fmt::Debug;
+        pub type APSupercow, InlineAPSupercow, APPhantomcow, InlineAPPhantomcow;
+    );
+
+    #[test]
+    fn features_generate_aliases() {
+        use std::rc::Rc;
+
+        let a: AliasedSupercow<String> =
+            Supercow::shared(Rc::new("foo".to_owned()));
+        let b = a.clone();
+        assert_eq!("foo", *b);
+
+        let c: InlineAliasedSupercow<u32> = Supercow::owned(42);
+        assert_eq!(42, *c);
+
+        let p: AliasedPhantomcow<String> = Supercow::phantom(a);
+        drop(p);
+        let p: InlineAliasedPhantomcow<u32> = Supercow::phantom(c);
+        drop(p);
+
+        let d: APSupercow<u32> = Supercow::shared(Rc::new(56));
+        assert_eq!(56, *d.clone());
+        let e: InlineAPSupercow<u32> = Supercow::owned(56);
+        assert_eq!(56, *e);
+        let _: APPhantomcow<u32> = Supercow::phantom(d);
+        let _: InlineAPPhantomcow<u32> = Supercow::phantom(e);
+    }
+
+    supercow_twostep!(unsafe struct TwoStepStdRc(::std::rc::Rc));
+    supercow_features!(
+        pub trait CustomTwoStepFeatures: Clone, TwoStepShared(TwoStepStdRc);
+        pub type CustomTwoStepSupercow, InlineCustomTwoStepSupercow,
+                 CustomTwoStepPhantomcow, InlineCustomTwoStepPhantomcow);
+    supercow_features!(
+        pub trait PathTwoStepFeatures: Clone, TwoStepShared(ext::TwoStepArc));
+
+    #[test]
+    fn custom_two_step_wrappers_share() {
+        let mut a: CustomTwoStepSupercow<String, str> =
+            Supercow::owned("foo".to_owned());
+        let b = Supercow::share(&mut a);
+        assert_eq!("foo", &*b);
+        assert_eq!(a.as_ptr(), b.as_ptr());
+
+        let mut c: InlineCustomTwoStepSupercow<u32> = Supercow::owned(42);
+        let d = Supercow::share(&mut c);
+        assert_eq!(42, *c);
+        assert_eq!(42, *d);
+        let _: CustomTwoStepPhantomcow<String, str> = Supercow::phantom(a);
+        let _: InlineCustomTwoStepPhantomcow<u32> = Supercow::phantom(c);
+
+        let mut e: Supercow<u32, u32,
+                            Box<dyn PathTwoStepFeatures<'static>>> =
+            Supercow::owned(56);
+        let f = Supercow::share(&mut e);
+        assert_eq!(56, *f);
+    }
+
+    #[test]
+    fn rc_supercow_shares_statically() {
+        use std::rc::Rc;
+
+        let mut a: RcSupercow<String, str> = Supercow::owned("foo".to_owned());
+        let b = Supercow::share(&mut a);
+        assert_eq!(a.as_ptr(), b.as_ptr());
+        match Supercow::extract_shared(&b) {
+            Some(ext::RcShared::TwoStep(r)) =>
+                assert_eq!(2, ext::TwoStepRc::strong_count(r)),
+            _ => panic!("Not shared through TwoStepRc"),
+        }
+
+        let c: RcSupercow<u32> = Supercow::shared(Rc::new(42));
+        let d = c.clone();
+        assert_eq!(42, *d);
+        let _: RcPhantomcow<u32> = Supercow::phantom(c);
+
+        assert_eq!(mem::size_of::<Rc<str>>(),
+                   mem::size_of::<ext::RcShared<String, str>>());
+    }
+
+    #[test]
+    fn inline_rc_supercow_stores_shared_inline() {
+        use std::rc::Rc;
+
+        let rc = Rc::new(42u32);
+        let a: InlineRcSupercow<u32> = Supercow::shared(rc.clone());
+        let b = a.clone();
+        assert_eq!(3, Rc::strong_count(&rc));
+        assert_eq!(42, *b);
+
+        // The shared reference lives inside the `Supercow` itself.
+        let shared = Supercow::extract_shared(&a).unwrap()
+            as *const ext::RcShared<u32, u32> as usize;
+        let base = &a as *const InlineRcSupercow<u32> as usize;
+        assert!(shared >= base &&
+                shared < base + mem::size_of::<InlineRcSupercow<u32>>());
+
+        let _: InlineRcPhantomcow<u32> = Supercow::phantom(a);
+    }
+
+    #[test]
+    fn lite_supercow_owned_and_borrowed() {
+        struct NotToOwned(u32);
+
+        let x = NotToOwned(42);
+        let a: LiteSupercow<NotToOwned> = Supercow::borrowed(&x);
+        let b: InlineLiteSupercow<NotToOwned> =
+            Supercow::owned(NotToOwned(56));
+        assert_eq!(42, a.0);
+        assert_eq!(56, b.0);
+        assert!(Supercow::clone_non_owned(&a).is_some());
+        assert!(Supercow::clone_non_owned(&b).is_none());
+        assert!(Supercow::extract_shared(&a).is_none());
+
+        let c: InlineLiteSupercow<String, str> =
+            Supercow::owned("foo".to_owned());
+        let d = c.clone();
+        assert_eq!("foo", &*d);
+        assert!(c.as_ptr() != d.as_ptr());
+
+        let _: LitePhantomcow<NotToOwned> = Supercow::phantom(a);
+        let _: InlineLitePhantomcow<NotToOwned> = Supercow::phantom(b);
+    }
+
+    #[test]
+    fn lite_supercow_is_smaller() {
+        assert!(mem::size_of::<InlineLiteSupercow<u8>>() <
+                mem::size_of::<InlineArcSupercow<u8>>());
+        assert!(mem::size_of::<InlineLiteSupercow<u64>>() <=
+                mem::size_of::<InlineSupercow<u64>>());
+        assert_eq!(mem::size_of::<ext::InlineStorage<u64, ext::NoShared>>(),
+                   mem::size_of::<u64>());
+    }
+
+    #[test]
+    fn inline_storage_has_no_discriminant() {
+        assert_eq!(mem::size_of::<u64>(),
+                   mem::size_of::<ext::InlineStorage<u64, u32>>());
+        assert_eq!(mem::size_of::<&u64>() + mem::size_of::<*mut ()>() +
+                   mem::size_of::<u64>(),
+                   mem::size_of::<InlineLiteSupercow<u64>>());
+    }
+
+    #[test]
+    fn share_arc_owned_supercow() {
+        use std::path::{Path, PathBuf};
+
+        let mut a: InlineArcSupercow<String, str> =
+            Supercow::owned("hello".into());
+        let b = Supercow::share_arc(&mut a);
+        assert_eq!("hello", &*a);
+        assert_eq!(a.as_ptr(), b.as_ptr());
+        assert!(Supercow::clone_non_owned(&a).is_some());
+
+        let mut a: InlineArcSupercow<Vec<u8>, [u8]> =
+            Supercow::owned(vec![1, 2, 3]);
+        let b = Supercow::share_arc(&mut a);
+        assert_eq!(&[1, 2, 3], &*b);
+        assert_eq!(a.as_ptr(), b.as_ptr());
+
+        let mut a: InlineArcSupercow<PathBuf, Path> =
+            Supercow::owned(PathBuf::from("/foo"));
+        let b = Supercow::share_arc(&mut a);
+        assert_eq!(Path::new("/foo"), &*b);
+        assert!(Supercow::clone_non_owned(&a).is_some());
+    }
+
+    #[test]
+    fn share_arc_borrowed_supercow() {
+        let mut a: InlineArcSupercow<String, str> = Supercow::borrowed("hello");
+        let b = Supercow::share_arc(&mut a);
+        assert_eq!(Some("hello"), Supercow::extract_ref(&a));
+        assert_eq!(Some("hello"), Supercow::extract_ref(&b));
+    }
+
+    #[test]
+    fn inline_storage_drops_exactly_once() {
+        let arc = Arc::new(42u32);
+        {
+            let _a: InlineSupercow<Arc<u32>> = Supercow::owned(arc.clone());
+            let b: InlineArcSupercow<Arc<u32>> = Supercow::owned(arc.clone());
+            let mut c: InlineArcSupercow<Arc<u32>> =
+                Supercow::owned(arc.clone());
+            let _d = Supercow::share(&mut c);
+            assert_eq!(4, Arc::strong_count(&arc));
+            drop(Supercow::into_inner(b));
+            assert_eq!(3, Arc::strong_count(&arc));
+        }
+        assert_eq!(1, Arc::strong_count(&arc));
+    }
+
+    #[test]
+    fn small_storage_size_is_bounded() {
+        type Tiny<'a, T> = SmallSupercow<'a, T, T, Arc<T>, 8>;
+
+        assert_eq!(mem::size_of::<Tiny<u8>>(),
+                   mem::size_of::<Tiny<[u64; 128]>>());
+        assert_eq!(mem::size_of::<SmallSupercow<u8>>(),
+                   mem::size_of::<SmallSupercow<SmallSupercow<String>>>());
+
+        let big: Tiny<[u64; 128]> = Supercow::owned([7; 128]);
+        let big = ::std::iter::once(big).next().unwrap();
+        assert_eq!(7, big[127]);
+    }
+
+    #[test]
+    fn small_storage_mixes_inline_and_boxed() {
+        // `OWNED` is inline while `SHARED` is boxed.
+        type Tiny<'a> = SmallSupercow<'a, u64, u64,
+                                      Box<dyn DefaultFeatures<'static>>, 8>;
+
+        let mut a: Tiny = Supercow::owned(42);
+        let moved = [Supercow::share(&mut a)];
+        assert_eq!(42, *a);
+        assert_eq!(42, *moved[0]);
+        *Supercow::to_mut(&mut a) = 56;
+        assert_eq!(56, *a);
+
+        let b: Tiny = Supercow::shared(Arc::new(99));
+        let b = vec![b].pop().unwrap();
+        assert_eq!(99, *b);
+    }
+
+    #[test]
+    fn small_storage_drops_exactly_once() {
+        type Tiny<'a, T> = SmallSupercow<'a, T, T,
+                                         Box<dyn DefaultFeatures<'static>>, 8>;
+
+        let arc = Arc::new(42u32);
+        {
+            let _a: Tiny<Arc<u32>> = Supercow::owned(arc.clone());
+            let b: Tiny<(Arc<u32>, [u64; 4])> =
+                Supercow::owned((arc.clone(), [0; 4]));
+            let mut c: SmallSupercow<Arc<u32>> = Supercow::owned(arc.clone());
+            let _d = Supercow::share(&mut c);
+            assert_eq!(4, Arc::strong_count(&arc));
+            drop(Supercow::into_inner(b));
+            assert_eq!(3, Arc::strong_count(&arc));
+        }
+        assert_eq!(1, Arc::strong_count(&arc));
+    }
+
+    #[test]
+    fn no_clone_features_accept_unique_owners() {
+        fn assert_send_sync<T : Send + Sync>(_: &T) { }
+
+        let a: NoCloneSupercow<u32> =
+            Supercow::shared(Box::new(Arc::new(42u32)));
+        assert_eq!(42, *a);
+        assert_send_sync(&a);
+        assert!(Supercow::extract_shared(&a).is_some());
+
+        let b: InlineNoCloneSupercow<u32> = Supercow::owned(56);
+        assert_eq!(56, *b);
+        let _: NoClonePhantomcow<u32> = Supercow::phantom(a);
+        let _: InlineNoClonePhantomcow<u32> = Supercow::phantom(b);
+    }
+
+    #[test]
+    fn no_clone_features_accept_non_clone_owners() {
+        // A uniquely-owned value which is neither `Clone` nor reference
+        // counted, standing in for something like a memory-mapped file.
+        struct Unique(Box<[u8]>);
+        unsafe impl ConstDeref for Unique {
+            type Target = [u8];
+            fn const_deref(&self) -> &[u8] { &self.0 }
+        }
+
+        let data: Box<[u8]> = vec![1, 2, 3].into_boxed_slice();
+        let ptr = data.as_ptr();
+        let a: NoCloneSupercow<Vec<u8>, [u8]> =
+            Supercow::shared(Unique(data));
+        assert_eq!(&[1, 2, 3], &*a);
+        assert_eq!(ptr, a.as_ptr());
+        assert!(Supercow::extract_shared(&a).is_some());
+
+        let b: InlineNoCloneSupercow<Vec<u8>, [u8]> =
+            Supercow::shared(Unique(vec![4].into_boxed_slice()));
+        assert_eq!(&[4], &*b);
+    }
+
+    #[test]
+    fn send_features_move_across_threads() {
+        let mut a: SendSupercow<String> = Supercow::owned("foo".to_owned());
+        let b = Supercow::share(&mut a);
+        let b = ::std::thread::spawn(move || {
+            assert_eq!("foo", *b);
+            b
+        }).join().unwrap();
+        assert_eq!("foo", *b);
+    }
+
+    #[test]
+    fn send_features_accept_send_only_shared() {
+        use std::cell::Cell;
+
+        // A shared handle which counts its own uses in a `Cell`, and is so
+        // `Send` but not `Sync`. `DefaultFeatures` requires `Sync`, so a plain
+        // `Supercow` cannot hold this.
+        struct Handle(Arc<u32>, Cell<usize>);
+        impl Clone for Handle {
+            fn clone(&self) -> Self {
+                Handle(self.0.clone(), Cell::new(0))
+            }
+        }
+        unsafe impl ConstDeref for Handle {
+            type Target = u32;
+            fn const_deref(&self) -> &u32 {
+                self.1.set(self.1.get() + 1);
+                &self.0
+            }
+        }
+
+        let a: SendSupercow<u32> =
+            Supercow::shared(Handle(Arc::new(42), Cell::new(0)));
+        let b = a.clone();
+        let b = ::std::thread::spawn(move || {
+            assert_eq!(42, *b);
+            b
+        }).join().unwrap();
+        assert_eq!(42, *a);
+        assert_eq!(42, *b);
+    }
+
+    #[test]
+    fn debug_features_print_shared() {
+        let mut a: DebugSupercow<u32> = Supercow::owned(42);
+        assert!(Supercow::extract_shared(&a).is_none());
+        let _b = Supercow::share(&mut a);
+        assert_eq!("Some(TwoStepArc(Some(42)))",
+                   format!("{:?}", Supercow::extract_shared(&a)));
+    }
+
+    #[test]
+    fn features_recognise_clone_paths_without_commata() {
+        let a: Supercow<u32, u32, Box<dyn ClonePathFeatures<'static>>> =
+            Supercow::shared(Arc::new(42u32));
+        let b = a.clone();
+        assert_eq!(42, *b);
+    }
+
+    #[test]
+    fn features_accept_paths_generics_and_lifetimes() {
+        fn assert_send_sync<T : Send + Sync>(_: &T) { }
+
+        let shared: Box<dyn PathFeatures<'static>> =
+            Box::new(Arc::new(42u32));
+        assert_eq!(&42, (*shared).as_ref());
+        assert_eq!("42", format!("{:?}", shared));
+
+        let a: Supercow<u32, u32, Box<dyn PathFeatures<'static>>> =
+            Supercow::shared(Arc::new(42u32));
+        let b = a.clone();
+        assert_send_sync(&b);
+        assert_eq!(42, *b);
+    }
+
+    #[test]
+    fn sync_string_supercow_into_boxed_error() {
+        fn fail() -> Result<(), Box<dyn Error + Send + Sync>> {
+            Err(Supercow::<String, str>::borrowed("sync"))?
+        }
+
+        assert_eq!("sync", fail().unwrap_err().to_string());
+    }
 }
 
 macro_rules! tests { ($modname:ident, $stype:ident, $ptype:ident) => {
@@ -2207,6 +4088,130 @@ mod $modname {
         assert_eq!(42u32, *x.as_ref());
     }
 
+    #[test]
+    fn operator_delegs_work() {
+        let a: $stype<u32> = Supercow::owned(6);
+        let b: $stype<u32> = Supercow::owned(4);
+        assert_eq!(10, &a + &b);
+        assert_eq!(2, &a - &b);
+        assert_eq!(24, &a * &b);
+        assert_eq!(1, &a / &b);
+        assert_eq!(2, &a % &b);
+        assert_eq!(4, &a & &b);
+        assert_eq!(6, &a | &b);
+        assert_eq!(2, &a ^ &b);
+        assert_eq!(96, &a << &b);
+        assert_eq!(0, &a >> &b);
+        assert_eq!(!6u32, !&a);
+
+        let c: $stype<i32> = Supercow::owned(42);
+        assert_eq!(-42, -&c);
+
+        let v = vec![1u32, 2, 3];
+        let d: $stype<Vec<u32>, [u32]> = Supercow::borrowed(&v);
+        assert_eq!(2, d[1]);
+        assert_eq!(&[2, 3], &d[1..]);
+    }
+
+    #[test]
+    fn assign_operators_take_ownership() {
+        let x = 42u32;
+        let mut a: $stype<u32> = Supercow::borrowed(&x);
+        a += 2;
+        assert_eq!(44, *a);
+        a -= 4;
+        a *= 3;
+        a /= 2;
+        a %= 7;
+        assert_eq!(4, *a);
+        a <<= 3;
+        a >>= 1;
+        a |= 1;
+        a &= 13;
+        a ^= 3;
+        assert_eq!(2, *a);
+        assert_eq!(42, x);
+
+        let mut s: $stype<String, str> = Supercow::borrowed("foo");
+        s += "bar";
+        assert_eq!("foobar", &*s);
+    }
+
+    #[test]
+    fn error_delegs_work() {
+        use std::error::Error;
+        use std::io;
+
+        let inner = io::Error::new(io::ErrorKind::Other, "oops");
+        let e: $stype<io::Error> = Supercow::borrowed(&inner);
+        assert_eq!("oops", e.to_string());
+        assert!(e.source().is_none());
+
+        #[derive(Debug)]
+        struct Wrapper(io::Error);
+        impl fmt::Display for Wrapper {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "wrapped")
+            }
+        }
+        impl Error for Wrapper {
+            fn source(&self) -> Option<&(dyn Error + 'static)> {
+                Some(&self.0)
+            }
+        }
+
+        drop(e);
+        let w: $stype<Wrapper> = Supercow::owned(Wrapper(inner));
+        assert_eq!("wrapped", w.to_string());
+        assert_eq!("oops", w.source().unwrap().to_string());
+    }
+
+    #[test]
+    fn string_supercow_into_boxed_error() {
+        use std::error::Error;
+
+        fn fail(n: u32) -> Result<(), Box<dyn Error>> {
+            if 0 == n {
+                Err($stype::<String, str>::borrowed("static message"))?;
+            }
+            let msg: $stype<String, str> =
+                Supercow::owned(format!("n = {}", n));
+            Err(msg)?
+        }
+
+        assert_eq!("static message", fail(0).unwrap_err().to_string());
+        assert_eq!("n = 5", fail(5).unwrap_err().to_string());
+    }
+
+    #[test]
+    fn option_has_no_overhead() {
+        assert_eq!(mem::size_of::<$stype<u32>>(),
+                   mem::size_of::<Option<$stype<u32>>>());
+        assert_eq!(mem::size_of::<$stype<String, str>>(),
+                   mem::size_of::<Option<$stype<String, str>>>());
+        assert_eq!(mem::size_of::<$ptype<u32>>(),
+                   mem::size_of::<Option<$ptype<u32>>>());
+
+        let x = 42u32;
+        let a: Option<$stype<u32>> = Some(Supercow::borrowed(&x));
+        assert_eq!(Some(42), a.map(|a| *a));
+        let b: Option<$stype<u32>> = Some(Supercow::owned(56));
+        assert_eq!(Some(56), b.map(|b| *b));
+    }
+
+    #[test]
+    fn into_inner_each_mode() {
+        let s: $stype<String> = Supercow::owned("owned".to_owned());
+        assert_eq!("owned", Supercow::into_inner(s));
+
+        let borrowed = "borrowed".to_owned();
+        let s: $stype<String> = Supercow::borrowed(&borrowed);
+        assert_eq!("borrowed", Supercow::into_inner(s));
+
+        let s: $stype<String> = Supercow::shared(Arc::new("shared".to_owned()));
+        assert_eq!("shared", Supercow::into_inner(s));
+    }
+
     #[test]
     fn owned_mode_survives_moving() {
         // Using a `HashMap` here because it means the optimiser can't reason
@@ -2489,6 +4494,162 @@ mod $modname {
         assert_eq!("hello world", &*b);
     }
 
+    #[test]
+    fn slice_owned_vec() {
+        let mut a: $stype<Vec<u8>, [u8]> = Supercow::owned(vec![1, 2, 3, 4]);
+        let b = Supercow::slice(&mut a, 1..3);
+
+        assert_eq!(&[1, 2, 3, 4], &*a);
+        assert_eq!(&[2, 3], &*b);
+        assert_eq!(a[1..].as_ptr(), b.as_ptr());
+        // `a` was moved into shared mode rather than remaining owned.
+        assert!(Supercow::clone_non_owned(&a).is_some());
+    }
+
+    #[test]
+    fn slice_borrowed_vec() {
+        let v = vec![1u8, 2, 3, 4];
+        let mut a: $stype<Vec<u8>, [u8]> = Supercow::borrowed(&v);
+        let b = Supercow::slice(&mut a, 2..);
+
+        assert_eq!(&[3, 4], &*b);
+        assert_eq!(v[2..].as_ptr(), b.as_ptr());
+        assert_eq!(Some(&v[..]), Supercow::extract_ref(&a));
+        assert_eq!(Some(&v[2..]), Supercow::extract_ref(&b));
+    }
+
+    #[test]
+    fn slice_shared_slice() {
+        let mut a: $stype<Vec<u8>, [u8]> = Supercow::owned(vec![1, 2, 3, 4]);
+        let mut b = Supercow::slice(&mut a, 1..);
+        let c = Supercow::slice(&mut b, 1..);
+        drop(a);
+        drop(b);
+
+        assert_eq!(&[3, 4], &*c);
+    }
+
+    #[test]
+    fn split_str() {
+        let mut a: $stype<String, str> =
+            Supercow::owned("hello world".to_owned());
+        let (b, c) = Supercow::split_at(&mut a, 5);
+        assert_eq!("hello", &*b);
+        assert_eq!(" world", &*c);
+
+        let d = Supercow::split_off(&mut a, 6);
+        assert_eq!("hello ", &*a);
+        assert_eq!("world", &*d);
+        assert_eq!(c[1..].as_ptr(), d.as_ptr());
+    }
+
+    #[test]
+    #[should_panic]
+    fn slice_str_checks_char_boundaries() {
+        let mut a: $stype<String, str> = Supercow::borrowed("h\u{e9}llo");
+        Supercow::slice(&mut a, 2..);
+    }
+
+    #[test]
+    fn default_is_owned() {
+        let a: $stype<String, str> = Default::default();
+        assert_eq!("", &*a);
+        assert!(Supercow::clone_non_owned(&a).is_none());
+
+        #[derive(Default)]
+        struct HasSupercow {
+            s: $stype<'static, Vec<u8>, [u8]>,
+        }
+        assert!(HasSupercow::default().s.is_empty());
+    }
+
+    #[test]
+    fn collect_into_supercow() {
+        use std::ffi::{OsStr, OsString};
+        use std::path::{Path, PathBuf};
+
+        let a: $stype<String, str> = vec!["foo", "bar"].into_iter().collect();
+        assert_eq!("foobar", &*a);
+
+        let b: $stype<Vec<u32>, [u32]> = (1..4).collect();
+        assert_eq!(&[1, 2, 3], &*b);
+
+        let c: $stype<PathBuf, Path> = vec!["foo", "bar"].into_iter().collect();
+        assert_eq!(Path::new("foo/bar"), &*c);
+
+        let d: $stype<OsString, OsStr> =
+            vec![OsStr::new("foo"), OsStr::new("bar")].into_iter().collect();
+        assert_eq!(OsStr::new("foobar"), &*d);
+    }
+
+    #[test]
+    fn extend_supercow() {
+        let mut a: $stype<String, str> = Supercow::borrowed("foo");
+        a.extend(Vec::<char>::new());
+        assert_eq!(Some("foo"), Supercow::extract_ref(&a));
+
+        a.extend(vec!['b', 'a', 'r']);
+        assert_eq!(None, Supercow::extract_ref(&a));
+        assert_eq!("foobar", &*a);
+
+        let v = vec![1u32];
+        let mut b: $stype<Vec<u32>, [u32]> = Supercow::borrowed(&v);
+        b.extend(2..4);
+        assert_eq!(&[1, 2, 3], &*b);
+        assert_eq!(&[1], &v[..]);
+    }
+
+    #[test]
+    fn fmt_write_to_supercow() {
+        use std::fmt::Write;
+
+        let mut a: $stype<String, str> = Supercow::borrowed("x = ");
+        write!(a, "").unwrap();
+        assert_eq!(Some("x = "), Supercow::extract_ref(&a));
+
+        write!(a, "{}", 42).unwrap();
+        assert_eq!("x = 42", &*a);
+    }
+
+    #[test]
+    fn read_from_supercow() {
+        use std::io::{BufRead, Cursor, Read, Seek, SeekFrom};
+
+        let data = vec![1u8, 2, 3, 4, 5];
+        let mut reader = Cursor::new(
+            $stype::<Vec<u8>, [u8]>::owned(data.clone()));
+        let mut buf = [0u8; 2];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!([1, 2], buf);
+
+        reader.seek(SeekFrom::End(-1)).unwrap();
+        assert_eq!(&[5], reader.fill_buf().unwrap());
+
+        let mut reader = Cursor::new(
+            $stype::<Vec<u8>, [u8]>::borrowed(&data));
+        reader.seek(SeekFrom::Start(3)).unwrap();
+        assert_eq!(data[3..].as_ptr(), reader.fill_buf().unwrap().as_ptr());
+    }
+
+    #[test]
+    fn write_to_supercow() {
+        use std::io::Write;
+
+        let data = vec![1u8, 2, 3];
+        let mut a: $stype<Vec<u8>, [u8]> = Supercow::borrowed(&data);
+        a.write_all(&[]).unwrap();
+        a.flush().unwrap();
+        assert_eq!(Some(&data[..]), Supercow::extract_ref(&a));
+
+        a.write_all(&[4, 5]).unwrap();
+        assert_eq!(None, Supercow::extract_ref(&a));
+        assert_eq!(&[1, 2, 3, 4, 5], &*a);
+        assert_eq!(&[1, 2, 3], &data[..]);
+
+        write!(a, "{}", 6).unwrap();
+        assert_eq!(&[1, 2, 3, 4, 5, b'6'], &*a);
+    }
+
     #[test]
     fn share_owned_phantomcow() {
         let sc: $stype<NotCloneable> = Supercow::owned(NotCloneable(42));
@@ -2524,3 +4685,8 @@ tests!(inline_sync_tests, InlineSupercow, InlinePhantomcow);
 tests!(inline_nonsync_tests, InlineNonSyncSupercow, InlineNonSyncPhantomcow);
 tests!(boxed_sync_tests, Supercow, Phantomcow);
 tests!(boxed_nonsync_tests, NonSyncSupercow, NonSyncPhantomcow);
+tests!(inline_send_tests, InlineSendSupercow, InlineSendPhantomcow);
+tests!(boxed_send_tests, SendSupercow, SendPhantomcow);
+tests!(inline_arc_tests, InlineArcSupercow, InlineArcPhantomcow);
+tests!(boxed_arc_tests, ArcSupercow, ArcPhantomcow);
+tests!(small_tests, SmallSupercow, SmallPhantomcow);
@@ -0,0 +1,479 @@
+// Copyright 2016 Jason Lingle
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A `Supercow` variant which is only a single pointer wide.
+//!
+//! `ThinSupercow` stores the ownership mode in bit 0 of its only field. In
+//! borrowed mode, that field is simply the `&'a BORROWED`. In owned or shared
+//! mode, it points to a heap allocation holding the owned or shared value
+//! alongside the borrowed pointer.
+//!
+//! ```
+//! use std::mem::size_of;
+//! use std::sync::Arc;
+//!
+//! use supercow::thin::ThinSupercow;
+//!
+//! let x = 42u32;
+//! let a: ThinSupercow<u32> = ThinSupercow::borrowed(&x);
+//! let mut b: ThinSupercow<u32> = ThinSupercow::owned(56);
+//! let c: ThinSupercow<u32> = ThinSupercow::shared(Arc::new(99));
+//! let d = ThinSupercow::share(&mut b);
+//! assert_eq!(42 + 56 + 99 + 56, *a + *b + *c + *d);
+//!
+//! assert_eq!(size_of::<&u32>(), size_of::<ThinSupercow<u32>>());
+//! assert_eq!(size_of::<&u32>(), size_of::<Option<ThinSupercow<u32>>>());
+//! ```
+//!
+//! The trade-offs compared to `Supercow` are:
+//!
+//! - `BORROWED` must be `Sized` and at least 2-byte aligned, so that the
+//!   borrowed pointer has a free low bit. This is checked at compile time by
+//!   every constructor, so the type cannot be used with such a `BORROWED`
+//!   in any mode.
+//!
+//! - Dereferencing an owned or shared `ThinSupercow` must first load the
+//!   borrowed pointer from the heap, so it is slower than with `Supercow`.
+//!
+//! - Storage is always boxed; there is no equivalent to `InlineStorage`.
+
+use std::borrow::Borrow;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::Deref;
+use std::ptr::{self, NonNull};
+
+use super::{DefaultFeatures, PfrExt, Ref};
+use super::ext::*;
+
+/// The boxed part of an owned or shared `ThinSupercow`.
+struct Heap<OWNED, BORROWED, SHARED> {
+    // The borrowed value. This either points into `inner` or somewhere
+    // `inner` refers to; neither moves while the `Heap` is alive since it is
+    // always boxed.
+    ptr: *const BORROWED,
+    inner: HeapInner<OWNED, SHARED>,
+}
+
+enum HeapInner<OWNED, SHARED> {
+    Owned(OWNED),
+    Shared(SHARED),
+}
+
+/// A `Supercow` for sized `BORROWED` types which is the size of a single
+/// reference.
+///
+/// See the module documentation for details.
+///
+/// A `BORROWED` with 1-byte alignment is rejected at compile time, whichever
+/// constructor is used:
+///
+/// ```compile_fail
+/// use supercow::thin::ThinSupercow;
+///
+/// let x = 42u8;
+/// let a: ThinSupercow<u8> = ThinSupercow::borrowed(&x);
+/// ```
+///
+/// ```compile_fail
+/// use supercow::thin::ThinSupercow;
+///
+/// let a: ThinSupercow<u8> = ThinSupercow::owned(42);
+/// ```
+pub struct ThinSupercow<'a, OWNED, BORROWED = OWNED,
+                        SHARED = Box<dyn DefaultFeatures<'static> + 'static>>
+where BORROWED : 'a {
+    // Either a `&'a BORROWED` (with bit 0 clear), or a `Box<Heap>` turned
+    // into a raw pointer with bit 0 set via `unalign2()`.
+    ptr: NonNull<()>,
+
+    _owned: PhantomData<OWNED>,
+    _borrowed: PhantomData<&'a BORROWED>,
+    _shared: PhantomData<SHARED>,
+}
+
+unsafe impl<'a, OWNED, BORROWED, SHARED> Send
+for ThinSupercow<'a, OWNED, BORROWED, SHARED>
+where OWNED : Send,
+      &'a BORROWED : Send,
+      SHARED : Send { }
+
+unsafe impl<'a, OWNED, BORROWED, SHARED> Sync
+for ThinSupercow<'a, OWNED, BORROWED, SHARED>
+where OWNED : Sync,
+      &'a BORROWED : Sync,
+      SHARED : Sync { }
+
+impl<'a, OWNED, BORROWED, SHARED> ThinSupercow<'a, OWNED, BORROWED, SHARED>
+where BORROWED : 'a {
+    // Borrowed pointers are stored untagged, so bit 0 must always be clear.
+    // Only `borrowed()` strictly needs this, but every constructor checks it
+    // so that the type is rejected consistently.
+    const BORROWED_ALIGNED: () = assert!(
+        mem::align_of::<BORROWED>() >= 2,
+        "ThinSupercow requires BORROWED to be at least 2-byte aligned");
+
+    /// Creates a new `ThinSupercow` which owns the given value.
+    pub fn owned(inner: OWNED) -> Self
+    where OWNED : SafeBorrow<BORROWED> {
+        let mut heap = Box::new(Heap {
+            ptr: ptr::null(),
+            inner: HeapInner::Owned(inner),
+        });
+        // If this panics, `heap` is simply dropped; nothing reads `ptr`.
+        if let HeapInner::Owned(ref o) = heap.inner {
+            heap.ptr = o.borrow();
+        }
+        Self::from_heap(heap)
+    }
+
+    /// Creates a new `ThinSupercow` which borrows the given value.
+    pub fn borrowed<T : Borrow<BORROWED> + ?Sized>(inner: &'a T) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::BORROWED_ALIGNED;
+        ThinSupercow {
+            ptr: NonNull::from(inner.borrow()).cast(),
+            _owned: PhantomData,
+            _borrowed: PhantomData,
+            _shared: PhantomData,
+        }
+    }
+
+    /// Creates a new `ThinSupercow` using the given shared reference.
+    ///
+    /// The reference must be convertible to `SHARED` via `SharedFrom`.
+    pub fn shared<T>(inner: T) -> Self
+    where T : ConstDeref<Target = BORROWED>,
+          SHARED : SharedFrom<T> {
+        let ptr: *const BORROWED = inner.const_deref();
+        Self::from_heap(Box::new(Heap {
+            ptr,
+            inner: HeapInner::Shared(SHARED::shared_from(inner)),
+        }))
+    }
+
+    fn from_heap(heap: Box<Heap<OWNED, BORROWED, SHARED>>) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::BORROWED_ALIGNED;
+        let raw = Box::into_raw(heap) as *mut ();
+        ThinSupercow {
+            // Safety: `Box::into_raw()` never returns null, and `unalign2()`
+            // only sets bit 0.
+            ptr: unsafe { NonNull::new_unchecked(raw.unalign2()) },
+            _owned: PhantomData,
+            _borrowed: PhantomData,
+            _shared: PhantomData,
+        }
+    }
+
+    fn heap(&self) -> Option<&Heap<OWNED, BORROWED, SHARED>> {
+        if self.ptr.as_ptr().is_2_aligned() {
+            None
+        } else {
+            // Safety: Bit 0 is only set on pointers from `from_heap()`.
+            Some(unsafe {
+                &*(self.ptr.as_ptr().align2()
+                   as *const Heap<OWNED, BORROWED, SHARED>)
+            })
+        }
+    }
+
+    fn heap_mut(&mut self) -> Option<&mut Heap<OWNED, BORROWED, SHARED>> {
+        if self.ptr.as_ptr().is_2_aligned() {
+            None
+        } else {
+            // Safety: Bit 0 is only set on pointers from `from_heap()`.
+            Some(unsafe {
+                &mut *(self.ptr.as_ptr().align2()
+                       as *mut Heap<OWNED, BORROWED, SHARED>)
+            })
+        }
+    }
+
+    /// Logically clone `this` without needing to clone `OWNED`.
+    ///
+    /// If this `ThinSupercow` is in owned mode, the owned value is first
+    /// moved into a new shared reference so that `OWNED` does not need to be
+    /// cloned.
+    pub fn share(this: &mut Self) -> Self
+    where OWNED : SafeBorrow<BORROWED>,
+          SHARED : Clone + TwoStepShared<OWNED, BORROWED> {
+        let heap = match this.heap_mut() {
+            Some(heap) => heap,
+            None => return ThinSupercow {
+                ptr: this.ptr,
+                _owned: PhantomData,
+                _borrowed: PhantomData,
+                _shared: PhantomData,
+            },
+        };
+
+        let owned_base = match heap.inner {
+            HeapInner::Owned(ref o) => o as *const OWNED as usize,
+            HeapInner::Shared(_) => return Self::from_heap(Box::new(Heap {
+                ptr: heap.ptr,
+                inner: Self::clone_shared(&heap.inner),
+            })),
+        };
+
+        let mut shared = SHARED::new_two_step();
+        // `deref_holder` is technically allowed to panic, but nothing has
+        // been changed yet if it does.
+        //
+        // Safety: `shared` was just produced by `new_two_step()`.
+        let holder: *mut Option<OWNED> = unsafe { shared.deref_holder() };
+
+        // These steps need to be uninterrupted by anything which could panic,
+        // since `heap.ptr` may dangle until the end.
+        //
+        // As in `Supercow::share()`, we can't call `borrow()` again on the
+        // moved value, so if the borrowed pointer pointed into the owned value
+        // it is rebased by hand. The holder is not moved by moving `shared`.
+        let old = mem::replace(&mut heap.inner, HeapInner::Shared(shared));
+        if let HeapInner::Owned(owned) = old {
+            // Safety: `holder` remains valid for as long as `shared` does,
+            // which is now in `heap.inner`.
+            let holder = unsafe { &mut *holder };
+            *holder = Some(owned);
+            if let Some(ref owned) = *holder {
+                let new_base = owned as *const OWNED as usize;
+                if heap.ptr.within(owned_base, mem::size_of::<OWNED>()) {
+                    heap.ptr = heap.ptr.rebase(owned_base, new_base);
+                }
+            }
+        }
+        // End uninterrupted section
+
+        Self::from_heap(Box::new(Heap {
+            ptr: heap.ptr,
+            inner: Self::clone_shared(&heap.inner),
+        }))
+    }
+
+    fn clone_shared(inner: &HeapInner<OWNED, SHARED>)
+                    -> HeapInner<OWNED, SHARED>
+    where SHARED : Clone {
+        match *inner {
+            HeapInner::Shared(ref s) => HeapInner::Shared(s.clone()),
+            HeapInner::Owned(_) => unreachable!(),
+        }
+    }
+
+    /// Returns a (indirect) mutable reference to an underlying owned value.
+    ///
+    /// If this `ThinSupercow` does not currently own the value, it takes
+    /// ownership. A `Ref` is then returned which allows accessing the mutable
+    /// owned value directly.
+    ///
+    /// ## Leak Safety
+    ///
+    /// If the returned `Ref` is released without its destructor being run,
+    /// the behaviour of the `ThinSupercow` is unspecified (but does not
+    /// result in memory unsafety).
+    pub fn to_mut<'b>(&'b mut self) -> Ref<'b, Self>
+    where OWNED : SafeBorrow<BORROWED>,
+          BORROWED : ToOwned<Owned = OWNED> {
+        // Become owned if not already.
+        let is_owned = matches!(
+            self.heap(), Some(&Heap { inner: HeapInner::Owned(_), .. }));
+        if !is_owned {
+            *self = Self::owned((**self).to_owned());
+        }
+
+        let r = match self.heap_mut() {
+            Some(&mut Heap { ref mut ptr, inner: HeapInner::Owned(ref mut o) })
+            => {
+                // Clear out `ptr` if it points somewhere unstable.
+                //
+                // Safety: `ptr` is valid for the lifetime of `self`; all we do
+                // here is turn it into a short-lived reference.
+                *ptr = OWNED::borrow_replacement(unsafe { &**ptr });
+                o as *mut OWNED
+            },
+            _ => unreachable!(),
+        };
+
+        Ref {
+            r,
+            parent: self,
+        }
+    }
+}
+
+impl<'a, OWNED, BORROWED, SHARED> RefParent
+for ThinSupercow<'a, OWNED, BORROWED, SHARED>
+where OWNED : SafeBorrow<BORROWED>,
+      BORROWED : 'a {
+    type Owned = OWNED;
+
+    unsafe fn supercow_ref_drop(&mut self) {
+        // Safety: Contract guarantees we are in owned mode and that there are
+        // no live borrows of the owned value remaining.
+        if let Some(&mut Heap { ref mut ptr, inner: HeapInner::Owned(ref o) })
+            = self.heap_mut()
+        {
+            *ptr = o.borrow();
+        }
+    }
+}
+
+impl<'a, OWNED, BORROWED, SHARED> Deref
+for ThinSupercow<'a, OWNED, BORROWED, SHARED>
+where BORROWED : 'a {
+    type Target = BORROWED;
+
+    #[inline]
+    fn deref(&self) -> &BORROWED {
+        match self.heap() {
+            // Safety: The heap pointer is valid as long as the heap is.
+            Some(heap) => unsafe { &*heap.ptr },
+            // Safety: Untagged pointers are always the original `&'a
+            // BORROWED`.
+            None => unsafe { &*(self.ptr.as_ptr() as *const BORROWED) },
+        }
+    }
+}
+
+impl<'a, OWNED, BORROWED, SHARED> Drop
+for ThinSupercow<'a, OWNED, BORROWED, SHARED>
+where BORROWED : 'a {
+    fn drop(&mut self) {
+        if let Some(heap) = self.heap_mut() {
+            // Safety: The heap was produced by `Box::into_raw()` in
+            // `from_heap()`, and is only released here.
+            drop(unsafe { Box::from_raw(heap as *mut Heap<_, _, _>) });
+        }
+    }
+}
+
+impl<'a, OWNED, BORROWED, SHARED> Clone
+for ThinSupercow<'a, OWNED, BORROWED, SHARED>
+where OWNED : Clone + SafeBorrow<BORROWED>,
+      SHARED : Clone,
+      BORROWED : 'a {
+    fn clone(&self) -> Self {
+        match self.heap() {
+            None => ThinSupercow {
+                ptr: self.ptr,
+                _owned: PhantomData,
+                _borrowed: PhantomData,
+                _shared: PhantomData,
+            },
+
+            Some(&Heap { inner: HeapInner::Owned(ref o), .. }) =>
+                Self::owned(o.clone()),
+
+            Some(&Heap { ptr, inner: HeapInner::Shared(ref s) }) =>
+                Self::from_heap(Box::new(Heap {
+                    ptr,
+                    inner: HeapInner::Shared(s.clone()),
+                })),
+        }
+    }
+}
+
+impl<'a, OWNED, BORROWED, SHARED> fmt::Debug
+for ThinSupercow<'a, OWNED, BORROWED, SHARED>
+where BORROWED : fmt::Debug + 'a {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::borrow::Borrow;
+    use std::mem;
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn single_word() {
+        assert_eq!(mem::size_of::<&u32>(),
+                   mem::size_of::<ThinSupercow<u32>>());
+        assert_eq!(mem::size_of::<&u32>(),
+                   mem::size_of::<Option<ThinSupercow<u32>>>());
+    }
+
+    #[test]
+    fn each_mode_derefs() {
+        let x = 42u32;
+        let a: ThinSupercow<u32> = ThinSupercow::borrowed(&x);
+        let b: ThinSupercow<u32> = ThinSupercow::owned(56);
+        let c: ThinSupercow<u32> = ThinSupercow::shared(Arc::new(99));
+        assert_eq!(&x as *const u32, &*a as *const u32);
+        assert_eq!(56, *b);
+        assert_eq!(99, *c);
+
+        let (a2, b2, c2) = (a.clone(), b.clone(), c.clone());
+        assert_eq!(&*a as *const u32, &*a2 as *const u32);
+        assert!(!ptr::eq(&*b, &*b2));
+        assert_eq!(&*c as *const u32, &*c2 as *const u32);
+        assert_eq!("56", format!("{:?}", b2));
+    }
+
+    #[test]
+    fn to_mut_takes_ownership() {
+        let x = 42u32;
+        let mut a: ThinSupercow<u32> = ThinSupercow::borrowed(&x);
+        *a.to_mut() += 1;
+        assert_eq!(43, *a);
+        assert_eq!(42, x);
+
+        let mut b: ThinSupercow<u32> = ThinSupercow::shared(Arc::new(56));
+        let c = b.clone();
+        *b.to_mut() += 1;
+        assert_eq!(57, *b);
+        assert_eq!(56, *c);
+    }
+
+    struct Internal(u32);
+    impl Borrow<u32> for Internal {
+        fn borrow(&self) -> &u32 { &self.0 }
+    }
+    unsafe impl SafeBorrow<u32> for Internal {
+        fn borrow_replacement(_: &u32) -> &u32 { &0 }
+    }
+
+    #[test]
+    fn share_rebases_internal_pointer() {
+        let mut a: ThinSupercow<Internal, u32> =
+            ThinSupercow::owned(Internal(42));
+        let b = ThinSupercow::share(&mut a);
+        assert_eq!(42, *a);
+        assert_eq!(42, *b);
+        assert_eq!(&*a as *const u32, &*b as *const u32);
+
+        let c = ThinSupercow::share(&mut a);
+        assert_eq!(&*a as *const u32, &*c as *const u32);
+    }
+
+    #[test]
+    fn share_borrowed() {
+        let x = 42u32;
+        let mut a: ThinSupercow<u32> = ThinSupercow::borrowed(&x);
+        let b = ThinSupercow::share(&mut a);
+        assert_eq!(&x as *const u32, &*b as *const u32);
+    }
+
+    #[test]
+    fn drops_owned_and_shared() {
+        let arc = Arc::new(42u32);
+        let a: ThinSupercow<Arc<u32>> = ThinSupercow::owned(arc.clone());
+        let mut b: ThinSupercow<Arc<u32>> = ThinSupercow::owned(arc.clone());
+        let c = ThinSupercow::share(&mut b);
+        assert_eq!(3, Arc::strong_count(&arc));
+        drop(a);
+        drop(b);
+        assert_eq!(2, Arc::strong_count(&arc));
+        drop(c);
+        assert_eq!(1, Arc::strong_count(&arc));
+    }
+}